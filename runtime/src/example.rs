@@ -1,25 +1,84 @@
-use crate::submitter::{PublicOf, SubmitAndSignTransaction};
+use crate::submitter::{note_offchain_block, PublicOf, SubmitAndSignTransaction, SubmitError};
 
+use codec::{Decode, Encode};
 use frame_support::{
-	debug, decl_event, decl_module, decl_storage, dispatch::DispatchResult, StorageValue,
+	debug, decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, ExistenceRequirement, Get, WithdrawReason},
+	IsSubType, StorageLinkedMap, StorageMap, StorageValue,
 };
-use sp_runtime::offchain::http;
-use sp_std::prelude::*;
+use sp_core::offchain::Duration;
+use sp_runtime::app_crypto::{AppKey, RuntimeAppPublic};
+use sp_runtime::offchain::{http, StorageKind};
+use sp_runtime::traits::{Hash, Saturating, SignedExtension, Zero};
+use sp_runtime::transaction_validity::{
+	InvalidTransaction, TransactionPriority, TransactionValidity, TransactionValidityError, ValidTransaction,
+};
+use sp_std::{fmt, marker::PhantomData, prelude::*};
 use system::ensure_signed;
 
 pub mod crypto {
 	use sp_core::crypto::KeyTypeId;
 	pub const KEY_TYPE: KeyTypeId = KeyTypeId(*b"reqs");
 
-	use sp_runtime::app_crypto::{app_crypto, sr25519};
-	app_crypto!(sr25519, KEY_TYPE);
+	/// `reqs` keys using sr25519, the default scheme.
+	pub mod sr25519 {
+		use sp_runtime::app_crypto::{app_crypto, sr25519};
+		app_crypto!(sr25519, super::KEY_TYPE);
+	}
+
+	/// `reqs` keys using ed25519, e.g. for operators with HSM-backed keys.
+	pub mod ed25519 {
+		use sp_runtime::app_crypto::{app_crypto, ed25519};
+		app_crypto!(ed25519, super::KEY_TYPE);
+	}
+
+	// There is no ecdsa application crypto (nor `MultiSigner` variant) in this Substrate
+	// version yet, so ecdsa keys cannot be supported here.
+
+	pub use self::sr25519::*;
 }
 
+/// How long the offchain worker waits for an HTTP response, in milliseconds.
+const FETCH_TIMEOUT: u64 = 3_000;
+
+/// Prefix of the offchain local storage keys recording the answers sent from this node.
+const ANSWER_KEY_PREFIX: &[u8] = b"example::answer";
+
+/// The public key type of the local keys used to sign oracle transactions.
+pub type LocalKeyOf<T> = PublicOf<
+	T,
+	<T as Trait>::Call,
+	<<T as Trait>::SubmitTransaction as SubmitAndSignTransaction<T, <T as Trait>::Call>>::SignAndSubmit,
+>;
+
+/// The balance type of the currency request fees are paid in.
+pub type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+
+/// The outcome of submitting an answer to a request on behalf of an authority account.
+pub type Submission<T> = (u64, <T as system::Trait>::AccountId, Result<(), SubmitError>);
+
 pub trait Trait: system::Trait {
 	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
 	type Call: From<Call<Self>>;
 
-	type SubmitTransaction: SubmitAndSignTransaction<Self, <Self as Trait>::Call>;
+	/// The `reqs` key type the authorities sign with, one of the schemes in `crypto`.
+	type AuthorityId: RuntimeAppPublic + AppKey;
+
+	/// Signs and submits the results, with local keys of type `AuthorityId`.
+	type SubmitTransaction: SubmitAndSignTransaction<Self, <Self as Trait>::Call, LocalKey = Self::AuthorityId>;
+
+	/// Number of blocks the designated authority has to answer a request before
+	/// every other authority steps in.
+	type GracePeriod: Get<Self::BlockNumber>;
+
+	/// Whether the offchain worker answers with every local authority key instead of
+	/// only the first one.
+	type SubmitWithAllKeys: Get<bool>;
+
+	/// The currency request fees are paid in.
+	type Currency: Currency<Self::AccountId>;
 }
 
 decl_event!(
@@ -27,17 +86,56 @@ decl_event!(
 	where
 		AccountId = <T as system::Trait>::AccountId,
 	{
-		Request(AccountId),
-		Offchain(AccountId),
+		/// An account made the request with the given id.
+		Request(AccountId, u64),
+		/// An authority answered the request with the given id.
+		Offchain(AccountId, u64),
 	}
 );
 
 decl_storage! {
 	trait Store for Module<T: Trait> as Example {
 		pub Authorities get(fn authorities) config(): Vec<T::AccountId> = vec![];
+		/// How many results are kept, the oldest ones being dropped first. Zero keeps them all.
+		pub MaxResults get(fn max_results) config(): u32;
+		/// How many authorities must answer a request before it settles, on the median answer.
+		/// Zero or one settles on the first answer.
+		pub Quorum get(fn quorum) config(): u32;
+		/// Fee burnt from the free balance of the requester by each request.
+		pub RequestFee get(fn request_fee) config(): BalanceOf<T>;
 
-		pub DataRequest get(fn data_request): Option<u64> = None;
-		pub Results get(fn results): Vec<u64> = vec![];
+		/// The id the next request gets.
+		pub NextRequestId get(fn next_request_id): u64;
+		/// Pending requests by id, with the block they were made in.
+		pub DataRequests get(fn data_request): linked_map u64 => Option<(u64, T::BlockNumber)>;
+		/// The answers to pending requests so far, until `Quorum` of them settle the request.
+		pub Answers get(fn answers): map u64 => Vec<(T::AccountId, u64)>;
+		/// Answers as `(request id, value)`, oldest first.
+		pub Results get(fn results): Vec<(u64, u64)> = vec![];
+	}
+	add_extra_genesis {
+		/// Values of the requests pending at genesis, numbered after the seeded results.
+		config(requests): Vec<u64>;
+		/// Answers to earlier requests, as `(request id, value)`, oldest first.
+		config(results): Vec<(u64, u64)>;
+		build(|config: &GenesisConfig<T>| {
+			assert!(config.quorum as usize <= config.authorities.len(), "Quorum larger than the authority set");
+			let mut ids = config.results.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+			ids.sort();
+			ids.dedup();
+			assert!(ids.len() == config.results.len(), "Results must answer distinct requests");
+			assert!(
+				config.max_results == 0 || config.results.len() <= config.max_results as usize,
+				"More results than MaxResults",
+			);
+
+			let first = ids.last().map_or(0, |id| id + 1);
+			for (i, value) in config.requests.iter().enumerate() {
+				<DataRequests<T>>::insert(first + i as u64, (*value, T::BlockNumber::zero()));
+			}
+			NextRequestId::put(first + config.requests.len() as u64);
+			Results::put(config.results.clone());
+		});
 	}
 }
 
@@ -45,59 +143,154 @@ decl_module! {
 	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
 		fn deposit_event() = default;
 
-		fn offchain_worker(_now: T::BlockNumber) {
+		fn offchain_worker(now: T::BlockNumber) {
 			debug::RuntimeLogger::init();
-			Self::offchain();
+			let _ = Self::offchain(now);
 		}
 
+		/// Request `value`, under the next request id announced in the `Request` event.
+		/// The requester pays `RequestFee`.
 		pub fn request(origin, value: u64) -> DispatchResult {
 			let who = ensure_signed(origin)?;
-			DataRequest::mutate(|x| *x = Some(value));
-			Self::deposit_event(RawEvent::Request(who));
+			let id = Self::next_request_id();
+			let next = id.checked_add(1).ok_or("Request id overflow")?;
+			let fee = Self::request_fee();
+			if !fee.is_zero() {
+				// Dropping the imbalance burns the fee.
+				T::Currency::withdraw(&who, fee, WithdrawReason::Fee.into(), ExistenceRequirement::KeepAlive)?;
+			}
+			NextRequestId::put(next);
+			<DataRequests<T>>::insert(id, (value, <system::Module<T>>::block_number()));
+			Self::deposit_event(RawEvent::Request(who, id));
 			Ok(())
 		}
 
-		pub fn store_result(origin, value: u64) -> DispatchResult {
+		/// Answer the pending request `id` with `value`. Only authorities may answer, once
+		/// each. The request settles on the median answer once `Quorum` authorities answered.
+		pub fn store_result(origin, id: u64, value: u64) -> DispatchResult {
 			let who = ensure_signed(origin)?;
-			DataRequest::mutate(|x| *x = None);
-			Results::mutate(|x| x.push(value));
-			Self::deposit_event(RawEvent::Offchain(who));
+			ensure!(Self::authorities().contains(&who), "Not an oracle authority");
+			ensure!(<DataRequests<T>>::exists(id), "Unknown or already answered request");
+			ensure!(!Self::has_answered(&who, id), "Already answered by this authority");
+			let mut answers = <Answers<T>>::get(id);
+			answers.push((who.clone(), value));
+			if answers.len() < Self::quorum().max(1) as usize {
+				<Answers<T>>::insert(id, answers);
+			} else {
+				let mut values = answers.into_iter().map(|(_, value)| value).collect::<Vec<_>>();
+				values.sort();
+				let median = values[(values.len() - 1) / 2];
+				<DataRequests<T>>::remove(id);
+				<Answers<T>>::remove(id);
+				Results::mutate(|results| {
+					results.push((id, median));
+					let max = Self::max_results() as usize;
+					if max > 0 && results.len() > max {
+						let excess = results.len() - max;
+						results.drain(..excess);
+					}
+				});
+			}
+			Self::deposit_event(RawEvent::Offchain(who, id));
 			Ok(())
 		}
 	}
 }
 
 impl<T: Trait> Module<T> {
-	pub fn offchain() {
-		let key = Self::authority_id();
-		let data_request = Self::data_request();
+	/// Answer the pending requests with the local authority keys expected to answer at `now`.
+	///
+	/// Returns the outcome of every submission, or `SubmitError::NoMatchingKey` if none of
+	/// the local keys may answer any request.
+	pub fn offchain(now: T::BlockNumber) -> Result<Vec<Submission<T>>, SubmitError> {
+		note_offchain_block::<T>(now);
+
+		if T::AuthorityId::all().is_empty() {
+			debug::warn!("No local authority keys");
+			return Err(SubmitError::NoMatchingKey);
+		}
 
-		if key.is_none() || data_request.is_none() {
-			debug::warn!("No authorized account or request");
-			return;
+		let requests = <DataRequests<T>>::enumerate().collect::<Vec<_>>();
+		if requests.is_empty() {
+			debug::warn!("No pending request");
+			return Ok(vec![]);
+		}
+
+		let mut results = vec![];
+		for (id, (value, _)) in requests {
+			debug::warn!("Start logic for #{:?}: {:?}", id, value);
+
+			// if let Ok(json) = Self::fetch_with_delay(
+			// 	"http://www.mocky.io/v2/5e0006ca2f0000780013b267?mocky-delay=10ms",
+			// ) {
+			// 	debug::warn!("Fetched data: {}", core::str::from_utf8(&json).unwrap());
+			// } else {
+			// 	debug::warn!("Error fetching with delay.");
+			// 	continue;
+			// }
+			let call = Call::store_result(id, value);
+			if T::SubmitWithAllKeys::get() {
+				let accounts = Self::authorities()
+					.into_iter()
+					.filter(|account| Self::should_answer(account, id, now) && !Self::answer_in_flight(id, account, now))
+					.collect::<Vec<_>>();
+				let submitted = T::SubmitTransaction::sign_and_submit_all(call, &accounts);
+				results.extend(submitted.into_iter().map(|(account, res)| (id, account, res)));
+			} else if let Some((account, key)) = Self::authority_id(id, now) {
+				if !Self::answer_in_flight(id, &account, now) {
+					results.push((id, account, T::SubmitTransaction::sign_and_submit(call, key)));
+				}
+			}
+		}
+		for (id, account, _) in results.iter().filter(|(_, _, res)| res.is_ok()) {
+			Self::note_answer(*id, account, now);
 		}
 
-		let value = data_request.unwrap();
-		debug::warn!("Start logic for #{:?}", value);
+		if results.is_empty() {
+			let authorities = Self::authorities();
+			if T::SubmitTransaction::get_local_keys().iter().any(|(account, _)| authorities.contains(account)) {
+				// Another authority is designated for every pending request until the grace
+				// period ends, or this node answered it already.
+				debug::debug!("No pending request to answer yet");
+			} else {
+				debug::warn!("No authorized account: {:?}", SubmitError::NoMatchingKey);
+			}
+			return Err(SubmitError::NoMatchingKey);
+		}
+		for (id, account, res) in &results {
+			debug::warn!("Finished #{:?} for {:?}: {:?}", id, account, res);
+		}
+		Ok(results)
+	}
+
+	fn answer_key(id: u64, account: &T::AccountId) -> Vec<u8> {
+		(ANSWER_KEY_PREFIX, id, account).encode()
+	}
+
+	/// Whether `account` answered the request `id` from this node less than `GracePeriod`
+	/// blocks before `now`.
+	///
+	/// Such an answer may still be included, and answering again would only be refused by the
+	/// pool or rejected on chain, leaving a gap in the nonces of `account`.
+	pub fn answer_in_flight(id: u64, account: &T::AccountId, now: T::BlockNumber) -> bool {
+		sp_io::offchain::local_storage_get(StorageKind::PERSISTENT, &Self::answer_key(id, account))
+			.and_then(|raw| T::BlockNumber::decode(&mut &raw[..]).ok())
+			.map_or(false, |at| now < at.saturating_add(T::GracePeriod::get()))
+	}
 
-		// if let Ok(json) = Self::fetch_with_delay(
-		// 	"http://www.mocky.io/v2/5e0006ca2f0000780013b267?mocky-delay=10ms",
-		// ) {
-		// 	debug::warn!("Fetched data: {}", core::str::from_utf8(&json).unwrap());
-		// } else {
-		// 	debug::warn!("Error fetching with delay.");
-		// 	return;
-		// }
-		let call = Call::store_result(value);
-		let res = T::SubmitTransaction::sign_and_submit(call, key.unwrap());
-		debug::warn!("Finished: {:?}", res);
+	fn note_answer(id: u64, account: &T::AccountId, now: T::BlockNumber) {
+		sp_io::offchain::local_storage_set(StorageKind::PERSISTENT, &Self::answer_key(id, account), &now.encode());
 	}
 
 	pub fn fetch_with_delay(url: &str) -> Result<Vec<u8>, http::Error> {
+		let deadline = sp_io::offchain::timestamp().add(Duration::from_millis(FETCH_TIMEOUT));
 		let pending = http::Request::get(url)
+			.deadline(deadline)
 			.send()
 			.map_err(|_| http::Error::IoError)?;
-		let response = pending.wait()?;
+		let response = pending
+			.try_wait(deadline)
+			.map_err(|_| http::Error::DeadlineReached)??;
 		if response.code != 200 {
 			debug::warn!("Unexpected status code: {}", response.code);
 			return Err(http::Error::Unknown);
@@ -116,44 +309,745 @@ impl<T: Trait> Module<T> {
 		Ok(json.as_bytes().to_vec())
 	}
 
-	pub fn authority_id() -> Option<
-		PublicOf<
-			T,
-			<T as Trait>::Call,
-			<<T as Trait>::SubmitTransaction as SubmitAndSignTransaction<T, <T as Trait>::Call>>::SignAndSubmit,
-		>,
-	> {
+	/// The authority expected to answer the pending request `id` first.
+	///
+	/// It is derived from the request id and the block the request was made in, so every
+	/// node agrees on it without any communication.
+	pub fn designated_authority(id: u64) -> Option<T::AccountId> {
+		let (_, requested_at) = Self::data_request(id)?;
+		let authorities = Self::authorities();
+		if authorities.is_empty() {
+			return None;
+		}
+		let seed = T::Hashing::hash_of(&(id, requested_at));
+		let index = u64::decode(&mut seed.as_ref()).unwrap_or_default() % authorities.len() as u64;
+		authorities.get(index as usize).cloned()
+	}
+
+	/// Whether `who` should answer the pending request `id` at block `now`, if it did not
+	/// already: either it is the designated authority, the grace period has passed without an
+	/// answer, or the quorum needs several answers anyway.
+	pub fn should_answer(who: &T::AccountId, id: u64, now: T::BlockNumber) -> bool {
+		let requested_at = match Self::data_request(id) {
+			Some((_, requested_at)) => requested_at,
+			None => return false,
+		};
+		if Self::has_answered(who, id) {
+			return false;
+		}
+		if Self::quorum() > 1 || now >= requested_at.saturating_add(T::GracePeriod::get()) {
+			return true;
+		}
+		Self::designated_authority(id).as_ref() == Some(who)
+	}
+
+	/// Whether `who` answered the pending request `id` already.
+	pub fn has_answered(who: &T::AccountId, id: u64) -> bool {
+		<Answers<T>>::get(id).iter().any(|(answered, _)| answered == who)
+	}
+
+	pub fn authority_id(id: u64, now: T::BlockNumber) -> Option<(T::AccountId, LocalKeyOf<T>)> {
 		let accounts = Self::authorities();
-		T::SubmitTransaction::get_local_keys().iter().find_map(|i| {
-			if accounts.contains(&i.0) {
-				Some(i.1.clone())
-			} else {
-				None
-			}
+		T::SubmitTransaction::get_local_keys()
+			.into_iter()
+			.find(|i| accounts.contains(&i.0) && Self::should_answer(&i.0, id, now))
+	}
+}
+
+/// Signed extension wrapping the fee payment extension `P` of the runtime.
+///
+/// `store_result` calls from accounts in `Authorities` bypass `P`, so they are free, and get
+/// the highest priority. They provide a tag per request id, so the pool keeps a single answer
+/// to each request, or one per authority when `Quorum` needs several. `store_result` calls from anyone else, or answering a request that is not
+/// pending (any more), are rejected before they reach the pool. Every other call is handled by
+/// `P` alone.
+#[derive(Encode, Decode, Clone, Eq, PartialEq)]
+pub struct ChargeUnlessOracle<T: Trait + Send + Sync, P>(P, PhantomData<T>);
+
+impl<T: Trait + Send + Sync, P> From<P> for ChargeUnlessOracle<T, P> {
+	fn from(inner: P) -> Self {
+		Self(inner, PhantomData)
+	}
+}
+
+impl<T: Trait + Send + Sync, P: fmt::Debug> fmt::Debug for ChargeUnlessOracle<T, P> {
+	#[cfg(feature = "std")]
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "ChargeUnlessOracle({:?})", self.0)
+	}
+
+	#[cfg(not(feature = "std"))]
+	fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+		Ok(())
+	}
+}
+
+impl<T: Trait + Send + Sync, P> ChargeUnlessOracle<T, P>
+where
+	<T as system::Trait>::Call: IsSubType<Module<T>, T>,
+{
+	/// The tag provided by the answer of `who` to the request `id`, so the pool keeps a single
+	/// answer per request, or per request and authority when the quorum needs several.
+	pub fn result_tag(id: u64, who: &T::AccountId) -> Vec<u8> {
+		if <Module<T>>::quorum() > 1 {
+			(b"example/result", id, who).encode()
+		} else {
+			(b"example/result", id).encode()
+		}
+	}
+
+	/// The request answered by `call`, if it is a `store_result` call.
+	fn store_result_id(call: &<T as system::Trait>::Call) -> Option<u64> {
+		match call.is_sub_type() {
+			Some(Call::store_result(id, _)) => Some(*id),
+			_ => None,
+		}
+	}
+}
+
+impl<T, P> SignedExtension for ChargeUnlessOracle<T, P>
+where
+	T: Trait + Send + Sync,
+	<T as system::Trait>::Call: IsSubType<Module<T>, T>,
+	P: SignedExtension<AccountId = T::AccountId, Call = <T as system::Trait>::Call>,
+{
+	type AccountId = T::AccountId;
+	type Call = <T as system::Trait>::Call;
+	type AdditionalSigned = P::AdditionalSigned;
+	type DispatchInfo = P::DispatchInfo;
+	type Pre = P::Pre;
+
+	fn additional_signed(&self) -> Result<Self::AdditionalSigned, TransactionValidityError> {
+		self.0.additional_signed()
+	}
+
+	fn validate(
+		&self,
+		who: &Self::AccountId,
+		call: &Self::Call,
+		info: Self::DispatchInfo,
+		len: usize,
+	) -> TransactionValidity {
+		let id = match Self::store_result_id(call) {
+			Some(id) => id,
+			None => return self.0.validate(who, call, info, len),
+		};
+		if !<Module<T>>::authorities().contains(who) {
+			return Err(InvalidTransaction::Call.into());
+		}
+		if !<DataRequests<T>>::exists(id) || <Module<T>>::has_answered(who, id) {
+			return Err(InvalidTransaction::Stale.into());
+		}
+		Ok(ValidTransaction {
+			priority: TransactionPriority::max_value(),
+			provides: vec![Self::result_tag(id, who)],
+			..Default::default()
 		})
 	}
+
+	fn pre_dispatch(
+		self,
+		who: &Self::AccountId,
+		call: &Self::Call,
+		info: Self::DispatchInfo,
+		len: usize,
+	) -> Result<Self::Pre, TransactionValidityError> {
+		if Self::store_result_id(call).is_none() {
+			return self.0.pre_dispatch(who, call, info, len);
+		}
+		self.validate(who, call, info, len).map(|_| Default::default())
+	}
+
+	fn post_dispatch(pre: Self::Pre, info: Self::DispatchInfo, len: usize) {
+		P::post_dispatch(pre, info, len)
+	}
 }
 
 #[cfg(test)]
 mod tests {
+	use super::{ChargeUnlessOracle, RawEvent};
+	use codec::{Decode, Encode};
+	use crate::submitter::{
+		next_nonce, note_offchain_block, nonce_key, SubmitAndSignTransaction, SubmitError, NONCE_EXPIRY,
+	};
 	use crate::testing::*;
-	use frame_support::assert_ok;
+	use frame_support::{assert_ok, traits::{Currency, Get}, weights::DispatchInfo, StorageValue};
+	use sp_runtime::offchain::http;
+	use sp_runtime::traits::SignedExtension;
+	use sp_runtime::transaction_validity::{InvalidTransaction, TransactionPriority, TransactionValidityError};
 
 	#[test]
 	fn it_works() {
-		let (mut ext, state) = exec_with_offchain();
+		let (mut ext, state, _) = exec_with_offchain();
 		ext.execute_with(|| {
-			assert_eq!(Example::data_request(), None);
+			System::set_block_number(1);
+			assert_eq!(Example::data_request(0), None);
 			assert_eq!(Example::results(), vec![]);
 
 			let origin = Origin::signed(42.into());
 			assert_ok!(Example::request(origin, 100));
 
-			assert_eq!(Example::data_request(), Some(100));
+			assert_eq!(Example::data_request(0), Some((100, 1)));
+			assert_eq!(events(), vec![TestEvent::example(RawEvent::Request(42.into(), 0))]);
+			clear_events();
 
 			seal_block(state.clone());
 
-			assert_eq!(Example::results(), vec![100]);
+			assert_eq!(Example::data_request(0), None);
+			assert_eq!(Example::results(), vec![(0, 100)]);
+			assert_eq!(events(), vec![TestEvent::example(RawEvent::Offchain(42.into(), 0))]);
+		});
+	}
+
+	#[test]
+	fn reports_dispatch_outcomes() {
+		let (mut ext, state, _) = exec_with_offchain();
+		ext.execute_with(|| {
+			assert_ok!(Example::request(Origin::signed(42.into()), 100));
+			// Answer once ahead of the block; the answer of `seal_block` is then rejected.
+			let answer = crate::example::Call::store_result(0, 100);
+			assert_eq!(SubmitTransaction::sign_and_submit(answer, 42.into()), Ok(()));
+
+			let dispatched = seal_block(state.clone()).unwrap();
+			assert_eq!(dispatched.len(), 2);
+			assert_eq!(dispatched[0].call, Call::Example(crate::example::Call::store_result(0, 100)));
+			assert_eq!(dispatched[0].origin, Some(42.into()));
+			assert!(dispatched[0].result.is_ok());
+			assert_eq!(dispatched[0].events.len(), 1);
+			assert_eq!(dispatched[0].events[0].event, TestEvent::example(RawEvent::Offchain(42.into(), 0)));
+			assert!(dispatched[1].result.is_err());
+			assert!(dispatched[1].events.is_empty());
+		});
+	}
+
+	#[test]
+	fn designated_authority_answers_first() {
+		let authorities: Vec<UintAuthorityId> = vec![1.into(), 2.into(), 3.into(), 4.into()];
+		let (mut ext, state, _) = exec_with_authorities(authorities.clone(), authorities.clone());
+		ext.execute_with(|| {
+			let origin = Origin::signed(1.into());
+			assert_ok!(Example::request(origin, 100));
+
+			let designated = Example::designated_authority(0).unwrap();
+			let other = authorities.iter().find(|a| **a != designated).unwrap().clone();
+			UintAuthorityId::set_all_keys(vec![other]);
+
+			// Only the designated authority may answer during the grace period.
+			for _ in 1..GracePeriod::get() {
+				assert_eq!(seal_block(state.clone()), None);
+				assert_eq!(Example::results(), vec![]);
+			}
+
+			// Afterwards every authority steps in.
+			assert_eq!(seal_block(state.clone()).map(|d| d.len()), Some(1));
+			assert_eq!(Example::results(), vec![(0, 100)]);
+		});
+	}
+
+	#[test]
+	fn answers_after_the_grace_period_over_full_blocks() {
+		let authorities: Vec<UintAuthorityId> = vec![1.into(), 2.into(), 3.into()];
+		let (mut ext, state, _) = exec_with_authorities(authorities.clone(), authorities.clone());
+		ext.execute_with(|| {
+			assert_ok!(Example::request(Origin::signed(1.into()), 100));
+
+			let designated = Example::designated_authority(0).unwrap();
+			let other = authorities.iter().find(|a| **a != designated).unwrap().clone();
+			UintAuthorityId::set_all_keys(vec![other.clone()]);
+
+			// The worker of the last block of the grace period answers, in the next block.
+			let mut chain = Chain::new(state.clone());
+			let blocks = chain.run_blocks(GracePeriod::get());
+			assert!(blocks.iter().all(|dispatched| dispatched.is_empty()));
+			assert_eq!(Example::results(), vec![]);
+
+			let blocks = chain.run_blocks(1);
+			assert_eq!(blocks[0].len(), 1);
+			assert_eq!(blocks[0][0].origin, Some(other.clone()));
+			let events = blocks[0][0].events.iter().map(|r| r.event.clone()).collect::<Vec<_>>();
+			assert_eq!(events, vec![TestEvent::example(RawEvent::Offchain(other, 0))]);
+			assert_eq!(chain.head(), GracePeriod::get() + 1);
+			assert_eq!(Example::results(), vec![(0, 100)]);
+		});
+	}
+
+	#[test]
+	fn answers_with_every_local_key() {
+		let authorities: Vec<UintAuthorityId> = vec![1.into(), 2.into(), 3.into()];
+		let local_keys: Vec<UintAuthorityId> = vec![1.into(), 2.into()];
+		let (mut ext, state, _) = exec_with_authorities(authorities, local_keys);
+		ext.execute_with(|| {
+			SubmitWithAllKeys::set(true);
+
+			let origin = Origin::signed(3.into());
+			assert_ok!(Example::request(origin, 100));
+
+			// Let the grace period pass so every authority is expected to answer.
+			let submitted = Example::offchain(GracePeriod::get()).unwrap();
+			assert_eq!(submitted.len(), 2);
+			assert_eq!(state.read().transactions.len(), 2);
+		});
+	}
+
+	#[test]
+	fn answers_several_requests_in_one_pass() {
+		let (mut ext, state, _) = exec_with_offchain();
+		ext.execute_with(|| {
+			assert_ok!(Example::request(Origin::signed(42.into()), 100));
+			assert_ok!(Example::request(Origin::signed(42.into()), 200));
+
+			let dispatched = seal_block(state.clone()).unwrap();
+			assert!(dispatched.iter().all(|d| d.result.is_ok()));
+			assert_eq!(next_nonce::<TestRuntime>(&42.into()), 2);
+
+			// Answers are included in submission order.
+			assert_eq!(Example::results(), vec![(0, 100), (1, 200)]);
+		});
+	}
+
+	#[test]
+	fn only_authorities_store_results() {
+		let (mut ext, _, _) = exec_with_offchain();
+		ext.execute_with(|| {
+			assert_ok!(Example::request(Origin::signed(1.into()), 100));
+			assert!(Example::store_result(Origin::signed(1.into()), 0, 100).is_err());
+			assert_eq!(Example::results(), vec![]);
+			assert_ok!(Example::store_result(Origin::signed(42.into()), 0, 100));
+		});
+	}
+
+	#[test]
+	fn oracle_results_are_free_and_prioritized() {
+		let (mut ext, _, _) = exec_with_offchain();
+		ext.execute_with(|| {
+			assert_ok!(Example::request(Origin::signed(1.into()), 100));
+			let extension = ChargeUnlessOracle::<TestRuntime, _>::from(NoFunds);
+			let store_result = Call::Example(crate::example::Call::store_result(0, 100));
+			let request = Call::Example(crate::example::Call::request(100));
+			let info = DispatchInfo::default();
+
+			let valid = extension.validate(&42.into(), &store_result, info, 0).unwrap();
+			assert_eq!(valid.priority, TransactionPriority::max_value());
+			assert_eq!(valid.provides, vec![ChargeUnlessOracle::<TestRuntime, NoFunds>::result_tag(0, &42.into())]);
+			assert_ok!(extension.clone().pre_dispatch(&42.into(), &store_result, info, 0));
+
+			assert_eq!(
+				extension.validate(&1.into(), &store_result, info, 0),
+				Err(InvalidTransaction::Call.into())
+			);
+			assert_eq!(
+				extension.validate(&42.into(), &request, info, 0),
+				Err(InvalidTransaction::Payment.into())
+			);
+		});
+	}
+
+	#[test]
+	fn results_for_unknown_requests_are_stale() {
+		let (mut ext, _, _) = exec_with_offchain();
+		ext.execute_with(|| {
+			let extension = ChargeUnlessOracle::<TestRuntime, _>::from(NoFunds);
+			let store_result = Call::Example(crate::example::Call::store_result(0, 100));
+			let info = DispatchInfo::default();
+			let stale = TransactionValidityError::from(InvalidTransaction::Stale);
+
+			assert_eq!(extension.validate(&42.into(), &store_result, info, 0), Err(stale));
+			assert_eq!(extension.clone().pre_dispatch(&42.into(), &store_result, info, 0), Err(stale));
+
+			assert_ok!(Example::request(Origin::signed(1.into()), 100));
+			assert_ok!(extension.clone().pre_dispatch(&42.into(), &store_result, info, 0));
+			assert_ok!(Example::store_result(Origin::signed(42.into()), 0, 100));
+			assert_eq!(extension.validate(&42.into(), &store_result, info, 0), Err(stale));
+		});
+	}
+
+	fn quorum_genesis() -> super::GenesisConfig<TestRuntime> {
+		super::GenesisConfig { authorities: vec![1.into(), 2.into(), 3.into()], quorum: 3, ..Default::default() }
+	}
+
+	#[test]
+	fn settles_on_the_median_once_the_quorum_answered() {
+		new_test_ext_with_genesis(quorum_genesis(), vec![]).execute_with(|| {
+			assert_ok!(Example::request(Origin::signed(1.into()), 100));
+			assert_ok!(Example::store_result(Origin::signed(1.into()), 0, 130));
+			assert_ok!(Example::store_result(Origin::signed(2.into()), 0, 90));
+			assert_eq!(Example::results(), vec![]);
+			assert_eq!(Example::answers(0), vec![(1.into(), 130), (2.into(), 90)]);
+
+			assert_ok!(Example::store_result(Origin::signed(3.into()), 0, 100));
+			assert_eq!(Example::results(), vec![(0, 100)]);
+			assert!(Example::data_request(0).is_none());
+			assert!(Example::answers(0).is_empty());
+		});
+	}
+
+	#[test]
+	fn authorities_answer_once_per_request() {
+		new_test_ext_with_genesis(quorum_genesis(), vec![]).execute_with(|| {
+			assert_ok!(Example::request(Origin::signed(1.into()), 100));
+			assert_ok!(Example::store_result(Origin::signed(1.into()), 0, 100));
+			assert!(Example::store_result(Origin::signed(1.into()), 0, 200).is_err());
+			assert_eq!(Example::answers(0), vec![(1.into(), 100)]);
+			assert!(!Example::should_answer(&1.into(), 0, 0));
+			assert!(Example::should_answer(&2.into(), 0, 0));
+
+			let extension = ChargeUnlessOracle::<TestRuntime, _>::from(NoFunds);
+			let store_result = Call::Example(crate::example::Call::store_result(0, 100));
+			let info = DispatchInfo::default();
+			assert_eq!(
+				extension.validate(&1.into(), &store_result, info, 0),
+				Err(InvalidTransaction::Stale.into())
+			);
+			// Each authority's answer has its own tag, so the pool keeps all of them.
+			let valid = extension.validate(&2.into(), &store_result, info, 0).unwrap();
+			assert_ne!(valid.provides, extension.validate(&3.into(), &store_result, info, 0).unwrap().provides);
+		});
+	}
+
+	#[test]
+	fn submits_signed_payloads_unsigned() {
+		let (mut ext, state, _) = exec_with_offchain();
+		ext.execute_with(|| {
+			let submitted = SubmitTransaction::sign_payload_and_submit_unsigned(
+				100u64,
+				&[42.into()],
+				|payload, signature| Call::Example(crate::example::Call::store_result(signature.0, payload)),
+			);
+			assert_eq!(submitted, vec![(UintAuthorityId(42), Ok(()))]);
+
+			// The test signature of a `u64` payload is the payload itself.
+			let transactions = pool_transactions(&state);
+			assert_eq!(transactions.len(), 1);
+			assert!(transactions[0].0.is_none());
+			assert_eq!(transactions[0].1, Call::Example(crate::example::Call::store_result(100, 100)));
+		});
+	}
+
+	const URL: &str = "http://localhost/value";
+
+	#[test]
+	fn fetches_responses() {
+		let (mut ext, _, offchain) = exec_with_offchain();
+		offchain.write().expect_get(URL, 200, b"{\"value\":100}");
+		ext.execute_with(|| {
+			assert_eq!(Example::fetch_with_delay(URL), Ok(b"{\"value\":100}".to_vec()));
+		});
+	}
+
+	#[test]
+	fn rejects_unexpected_status() {
+		let (mut ext, _, offchain) = exec_with_offchain();
+		offchain.write().expect_get(URL, 404, b"Not Found");
+		ext.execute_with(|| {
+			assert_eq!(Example::fetch_with_delay(URL), Err(http::Error::Unknown));
+		});
+	}
+
+	#[test]
+	fn rejects_non_utf8_body() {
+		let (mut ext, _, offchain) = exec_with_offchain();
+		offchain.write().expect_get(URL, 200, &[0xff, 0xfe, 0xfd]);
+		ext.execute_with(|| {
+			assert_eq!(Example::fetch_with_delay(URL), Err(http::Error::Unknown));
+		});
+	}
+
+	#[test]
+	fn gives_up_after_timeout() {
+		let (mut ext, _, offchain) = exec_with_offchain();
+		offchain.write().expect_timeout(URL);
+		ext.execute_with(|| {
+			assert_eq!(Example::fetch_with_delay(URL), Err(http::Error::DeadlineReached));
+		});
+		assert_eq!(offchain.read().timestamp, super::FETCH_TIMEOUT);
+	}
+
+	#[test]
+	fn only_the_designated_node_answers() {
+		let authorities: Vec<UintAuthorityId> = vec![1.into(), 2.into(), 3.into()];
+		let nodes = authorities.iter().map(|a| vec![a.clone()]).collect();
+		let mut network = Network::new(authorities, nodes);
+		network.execute_with(|| {
+			assert_ok!(Example::request(Origin::signed(1.into()), 100));
+		});
+
+		let included = network.seal_block();
+		assert_eq!(included.len(), 1);
+		assert!(included[0].result.is_ok());
+		network.execute_with(|| {
+			assert_eq!(Example::results(), vec![(0, 100)]);
+		});
+	}
+
+	#[test]
+	fn late_answers_are_rejected() {
+		let authorities: Vec<UintAuthorityId> = vec![1.into(), 2.into(), 3.into()];
+		let nodes = authorities.iter().map(|a| vec![a.clone()]).collect();
+		let mut network = Network::new(authorities, nodes);
+		network.execute_with(|| {
+			assert_ok!(Example::request(Origin::signed(1.into()), 100));
+			// Let the grace period pass so every node answers.
+			System::set_block_number(GracePeriod::get());
+		});
+
+		let included = network.seal_block();
+		assert_eq!(included.len(), 3);
+		assert_eq!(included.iter().filter(|d| d.result.is_ok()).count(), 1);
+		network.execute_with(|| {
+			assert_eq!(Example::results(), vec![(0, 100)]);
+		});
+	}
+
+	#[test]
+	fn includes_duplicate_transactions_once() {
+		let mut network = Network::new(vec![42.into()], vec![vec![42.into()], vec![42.into()]]);
+		network.execute_with(|| {
+			assert_ok!(Example::request(Origin::signed(42.into()), 100));
+		});
+
+		let included = network.seal_block();
+		assert_eq!(included.len(), 1);
+		assert_eq!(included[0].origin, Some(42.into()));
+	}
+
+	#[test]
+	fn signs_results_with_real_keys() {
+		let (mut ext, state, authorities) = signed::sr25519::exec_with_keystore(&["//Alice", "//Bob"]);
+		ext.execute_with(|| {
+			assert_ok!(signed::sr25519::Example::request(signed::sr25519::Origin::signed(authorities[0]), 100));
+
+			assert_eq!(signed::sr25519::seal_block(state.clone()), vec![signed::Included::Dispatched(Ok(()))]);
+			assert_eq!(signed::sr25519::Example::results(), vec![(0, 100)]);
+		});
+	}
+
+	#[test]
+	fn tampered_results_do_not_verify() {
+		let (mut ext, state, authorities) = signed::sr25519::exec_with_keystore(&["//Alice"]);
+		ext.execute_with(|| {
+			assert_ok!(signed::sr25519::Example::request(signed::sr25519::Origin::signed(authorities[0]), 100));
+			assert_ok!(signed::sr25519::Example::offchain(1));
+
+			let transaction = state.read().transactions[0].clone();
+			let mut xt: signed::sr25519::SignedXt = Decode::decode(&mut &*transaction).unwrap();
+			assert!(xt.is_valid());
+
+			xt.call = signed::sr25519::Call::Example(crate::example::Call::store_result(0, 999));
+			assert!(!xt.is_valid());
+		});
+	}
+
+	#[test]
+	fn signs_results_with_ed25519_keys() {
+		let (mut ext, state, authorities) = signed::ed25519::exec_with_keystore(&["//Alice", "//Bob"]);
+		ext.execute_with(|| {
+			assert_ok!(signed::ed25519::Example::request(signed::ed25519::Origin::signed(authorities[0]), 100));
+
+			assert_eq!(signed::ed25519::seal_block(state.clone()), vec![signed::Included::Dispatched(Ok(()))]);
+			assert_eq!(signed::ed25519::Example::results(), vec![(0, 100)]);
+		});
+	}
+
+	#[test]
+	fn reports_submission_outcomes() {
+		let (mut ext, _, _) = exec_with_offchain();
+		ext.execute_with(|| {
+			let origin = Origin::signed(42.into());
+			assert_ok!(Example::request(origin, 100));
+
+			assert_eq!(Example::offchain(1), Ok(vec![(0, UintAuthorityId(42), Ok(()))]));
+
+			UintAuthorityId::set_all_keys(Vec::<UintAuthorityId>::new());
+			assert_eq!(Example::offchain(1), Err(SubmitError::NoMatchingKey));
+		});
+	}
+
+	#[test]
+	fn reports_pool_rejections() {
+		let (mut ext, state, _) = exec_with_offchain();
+		ext.execute_with(|| {
+			let origin = Origin::signed(42.into());
+			assert_ok!(Example::request(origin, 100));
+
+			for rejection in vec![Rejection::Full, Rejection::Invalid, Rejection::AlreadyImported] {
+				state.write().reject_next(rejection);
+				assert_eq!(Example::offchain(1), Ok(vec![(0, UintAuthorityId(42), Err(SubmitError::Pool))]));
+				assert_eq!(state.read().rejected.last().map(|(_, r)| *r), Some(rejection));
+			}
+			assert!(state.read().transactions.is_empty());
+			// Rejected transactions do not use up a nonce.
+			assert_eq!(next_nonce::<TestRuntime>(&42.into()), 0);
+		});
+	}
+
+	#[test]
+	fn retries_after_the_pool_rejects() {
+		let (mut ext, state, _) = exec_with_offchain();
+		ext.execute_with(|| {
+			let origin = Origin::signed(42.into());
+			assert_ok!(Example::request(origin, 100));
+
+			state.write().reject_next(Rejection::Full);
+			assert_eq!(seal_block(state.clone()), Some(vec![]));
+			assert_eq!(Example::results(), vec![]);
+
+			// The request is still pending, so the worker of the next block submits again.
+			let dispatched = seal_block(state.clone()).unwrap();
+			assert_eq!(dispatched.len(), 1);
+			assert!(dispatched[0].result.is_ok());
+			assert_eq!(Example::results(), vec![(0, 100)]);
+			assert_eq!(state.read().rejected.len(), 1);
+		});
+	}
+
+	#[test]
+	fn controls_the_offchain_clock_and_randomness() {
+		let (mut ext, _, offchain) = exec_with_offchain();
+		ext.execute_with(|| {
+			offchain.write().set_timestamp(1_000);
+			assert_eq!(sp_io::offchain::timestamp().unix_millis(), 1_000);
+			offchain.write().advance_timestamp(500);
+			assert_eq!(sp_io::offchain::timestamp().unix_millis(), 1_500);
+
+			offchain.write().set_random_seed([7; 32]);
+			assert_eq!(sp_io::offchain::random_seed(), [7; 32]);
+		});
+	}
+
+	#[test]
+	fn inspects_local_storage_between_blocks() {
+		let (mut ext, state, offchain) = exec_with_offchain();
+		ext.execute_with(|| {
+			let key = nonce_key::<TestRuntime>(&42.into());
+			assert_eq!(offchain.read().persistent_storage(&key), None);
+
+			assert_ok!(Example::request(Origin::signed(42.into()), 100));
+			seal_block(state.clone()).unwrap();
+			// The next nonce, the on-chain nonce, and the block since which it has not moved.
+			assert_eq!(offchain.read().persistent_storage(&key), Some((1u64, 0u64, 1u64).encode()));
+
+			assert_ok!(Example::request(Origin::signed(42.into()), 200));
+			seal_block(state.clone()).unwrap();
+			// The mock does not check nonces, so the on-chain nonce never moves.
+			assert_eq!(offchain.read().persistent_storage(&key), Some((2u64, 0u64, 1u64).encode()));
+		});
+	}
+
+	#[test]
+	fn forgets_dropped_transactions_eventually() {
+		let (mut ext, state, _) = exec_with_offchain();
+		ext.execute_with(|| {
+			assert_ok!(Example::request(Origin::signed(42.into()), 100));
+			assert_ok!(Example::offchain(1));
+			// The transaction never makes it into a block.
+			state.write().transactions.clear();
+
+			// Later submissions do not push the expiry back.
+			let last = NONCE_EXPIRY as u64;
+			note_offchain_block::<TestRuntime>(last);
+			let answer = crate::example::Call::store_result(0, 100);
+			assert_eq!(SubmitTransaction::sign_and_submit(answer, 42.into()), Ok(()));
+			assert_eq!(next_nonce::<TestRuntime>(&42.into()), 2);
+
+			note_offchain_block::<TestRuntime>(last + 1);
+			assert_eq!(next_nonce::<TestRuntime>(&42.into()), 0);
+		});
+	}
+
+	#[test]
+	fn does_not_answer_twice_while_the_answer_is_pending() {
+		let (mut ext, state, _) = exec_with_offchain();
+		ext.execute_with(|| {
+			assert_ok!(Example::request(Origin::signed(42.into()), 100));
+			assert_ok!(Example::offchain(1));
+			assert!(Example::answer_in_flight(0, &42.into(), 1));
+
+			// The answer missed the next blocks, but may still be included.
+			for now in 2..1 + GracePeriod::get() {
+				assert_eq!(Example::offchain(now), Err(SubmitError::NoMatchingKey));
+			}
+			assert_eq!(state.read().transactions.len(), 1);
+
+			// Past the grace period, the answer is sent again.
+			assert_eq!(Example::offchain(1 + GracePeriod::get()), Ok(vec![(0, UintAuthorityId(42), Ok(()))]));
+			assert_eq!(state.read().transactions.len(), 2);
+		});
+	}
+
+	fn full_genesis() -> super::GenesisConfig<TestRuntime> {
+		super::GenesisConfig {
+			authorities: vec![1.into(), 2.into()],
+			max_results: 3,
+			quorum: 2,
+			request_fee: 5,
+			requests: vec![100, 200],
+			results: vec![(0, 10), (4, 40)],
+		}
+	}
+
+	#[test]
+	fn builds_the_full_genesis() {
+		let config = full_genesis();
+		let json = serde_json::to_string(&config).unwrap();
+		let decoded: super::GenesisConfig<TestRuntime> = serde_json::from_str(&json).unwrap();
+		assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+
+		new_test_ext_with_genesis(decoded, vec![1.into()]).execute_with(|| {
+			assert_eq!(Example::authorities(), vec![1.into(), 2.into()]);
+			assert_eq!(Example::max_results(), 3);
+			assert_eq!(Example::quorum(), 2);
+			assert_eq!(Example::request_fee(), 5);
+			assert_eq!(Example::results(), vec![(0, 10), (4, 40)]);
+			// Pending requests are numbered after the seeded results.
+			assert_eq!(Example::data_request(5), Some((100, 0)));
+			assert_eq!(Example::data_request(6), Some((200, 0)));
+			assert_eq!(Example::next_request_id(), 7);
+		});
+	}
+
+	#[test]
+	#[should_panic(expected = "Quorum larger than the authority set")]
+	fn rejects_unreachable_quorum() {
+		new_test_ext_with_genesis(super::GenesisConfig { quorum: 3, ..full_genesis() }, vec![]);
+	}
+
+	#[test]
+	#[should_panic(expected = "Results must answer distinct requests")]
+	fn rejects_duplicate_results() {
+		let results = vec![(0, 10), (0, 20)];
+		new_test_ext_with_genesis(super::GenesisConfig { results, ..full_genesis() }, vec![]);
+	}
+
+	#[test]
+	#[should_panic(expected = "More results than MaxResults")]
+	fn rejects_too_many_results() {
+		new_test_ext_with_genesis(super::GenesisConfig { max_results: 1, ..full_genesis() }, vec![]);
+	}
+
+	#[test]
+	fn requests_burn_the_request_fee() {
+		let config = super::GenesisConfig { request_fee: 30, ..Default::default() };
+		new_test_ext_with_genesis(config, vec![]).execute_with(|| {
+			let _ = Balances::deposit_creating(&1.into(), 100);
+			assert_ok!(Example::request(Origin::signed(1.into()), 100));
+			assert_eq!(Balances::free_balance(&1.into()), 70);
+			assert_eq!(Balances::total_issuance(), 70);
+
+			assert!(Example::request(Origin::signed(2.into()), 100).is_err());
+			assert_eq!(Example::next_request_id(), 1);
+		});
+	}
+
+	#[test]
+	fn drops_the_oldest_results_past_max_results() {
+		let (mut ext, state, _) = exec_with_offchain();
+		ext.execute_with(|| {
+			super::MaxResults::put(2);
+			for value in vec![100, 200, 300] {
+				assert_ok!(Example::request(Origin::signed(42.into()), value));
+				seal_block(state.clone()).unwrap();
+			}
+			assert_eq!(Example::results(), vec![(1, 200), (2, 300)]);
 		});
 	}
 }
@@ -228,10 +228,28 @@ impl sudo::Trait for Runtime {
 	type Proposal = Call;
 }
 
+parameter_types! {
+	pub const GracePeriod: BlockNumber = 5;
+	pub const SubmitWithAllKeys: bool = false;
+	/// Number of blocks a transaction created by the offchain worker stays valid for.
+	pub const TransactionMortality: u64 = 64;
+}
+
 impl example::Trait for Runtime {
 	type Event = Event;
 	type Call = Call;
-	type SubmitTransaction = submitter::TransactionSubmitter<example::crypto::Public, Runtime, UncheckedExtrinsic>;
+	/// Oracle keys use sr25519; `example::crypto::ed25519::Public` works as well.
+	type AuthorityId = example::crypto::sr25519::Public;
+	type SubmitTransaction = submitter::TransactionSubmitter<
+		<Runtime as example::Trait>::AuthorityId,
+		Runtime,
+		UncheckedExtrinsic,
+	>;
+	/// Blocks the designated authority has to answer before the others step in.
+	type GracePeriod = GracePeriod;
+	/// Answer with the first local authority key only.
+	type SubmitWithAllKeys = SubmitWithAllKeys;
+	type Currency = Balances;
 }
 
 impl offchain::CreateTransaction<Runtime, UncheckedExtrinsic> for Runtime {
@@ -244,16 +262,12 @@ impl offchain::CreateTransaction<Runtime, UncheckedExtrinsic> for Runtime {
 		account: AccountId,
 		index: Index,
 	) -> Option<(Call, <UncheckedExtrinsic as traits::Extrinsic>::SignaturePayload)> {
-		let tip = 0;
-		let extra: SignedExtra = (
-			system::CheckVersion::<Runtime>::new(),
-			system::CheckGenesis::<Runtime>::new(),
-			system::CheckEra::<Runtime>::from(generic::Era::immortal()),
-			system::CheckNonce::<Runtime>::from(index),
-			system::CheckWeight::<Runtime>::new(),
-			transaction_payment::ChargeTransactionPayment::<Runtime>::from(tip),
-		);
-		let raw_payload = SignedPayload::new(call, extra).ok()?;
+		// The transaction is valid from the last block with a known hash on. `System` is not
+		// initialised in the offchain worker, so this goes by the block the worker runs for.
+		let current_block = (submitter::offchain_block::<Runtime>() as u64).saturating_sub(1);
+		let era = generic::Era::mortal(TransactionMortality::get(), current_block);
+		// No tip: `ChargeUnlessOracle` lets authorities answer for free.
+		let raw_payload = SignedPayload::new(call, signed_extra(era, index, 0)).ok()?;
 		let signature = TSigner::sign(public, &raw_payload)?;
 		let address = Indices::unlookup(account);
 		let (call, extra, _) = raw_payload.deconstruct();
@@ -297,8 +311,24 @@ pub type SignedExtra = (
 	system::CheckEra<Runtime>,
 	system::CheckNonce<Runtime>,
 	system::CheckWeight<Runtime>,
-	transaction_payment::ChargeTransactionPayment<Runtime>,
+	// Oracle authorities answer for free, everyone else pays transaction fees.
+	example::ChargeUnlessOracle<Runtime, transaction_payment::ChargeTransactionPayment<Runtime>>,
 );
+/// The `SignedExtra` of a transaction valid during `era`, with the given nonce and tip.
+pub fn signed_extra(era: generic::Era, nonce: Index, tip: Balance) -> SignedExtra {
+	(
+		system::CheckVersion::<Runtime>::new(),
+		system::CheckGenesis::<Runtime>::new(),
+		system::CheckEra::<Runtime>::from(era),
+		system::CheckNonce::<Runtime>::from(nonce),
+		system::CheckWeight::<Runtime>::new(),
+		transaction_payment::ChargeTransactionPayment::<Runtime>::from(tip).into(),
+	)
+}
+/// The storage key of the block timestamp, `Timestamp::now`, for clients reading it from the state.
+pub fn timestamp_now_key() -> Vec<u8> {
+	<timestamp::Now<Runtime> as frame_support::storage::generator::StorageValue<u64>>::storage_value_final_key().to_vec()
+}
 /// The payload being signed in transactions.
 pub type SignedPayload = generic::SignedPayload<Call, SignedExtra>;
 /// Unchecked extrinsic type as expected by this runtime.
@@ -1,5 +1,8 @@
+use codec::{Decode, Encode};
 use sp_runtime::app_crypto::{AppPublic, RuntimeAppPublic};
-use sp_runtime::traits::{Extrinsic as ExtrinsicT, IdentifyAccount};
+use sp_runtime::offchain::StorageKind;
+use sp_runtime::traits::{Extrinsic as ExtrinsicT, IdentifyAccount, One, Saturating};
+use sp_runtime::RuntimeDebug;
 use sp_std::prelude::Vec;
 use system::offchain::*;
 use system::*;
@@ -9,6 +12,132 @@ pub type PublicOf<T, Call, X> = <<X as SubmitSignedTransaction<T, Call>>::Create
     <X as SubmitSignedTransaction<T, Call>>::Extrinsic,
 >>::Public;
 
+pub type SignatureOf<T, Call, X> = <<X as SubmitSignedTransaction<T, Call>>::CreateTransaction as CreateTransaction<
+    T,
+    <X as SubmitSignedTransaction<T, Call>>::Extrinsic,
+>>::Signature;
+
+/// Storage key holding the block the offchain worker runs for, see `note_offchain_block`.
+const OFFCHAIN_BLOCK_KEY: &[u8] = b"submitter::offchain_block";
+
+/// Record the block the offchain worker runs for.
+///
+/// `Executive::offchain_worker` does not initialise `System`, whose block number reads zero
+/// there. The value is written to the worker's own state overlay, which is discarded when
+/// the worker ends.
+pub fn note_offchain_block<T: Trait>(now: T::BlockNumber) {
+    sp_io::storage::set(OFFCHAIN_BLOCK_KEY, &now.encode());
+}
+
+/// The block the offchain worker runs for, or `System::block_number()` outside of it.
+pub fn offchain_block<T: Trait>() -> T::BlockNumber {
+    sp_io::storage::get(OFFCHAIN_BLOCK_KEY)
+        .and_then(|raw| T::BlockNumber::decode(&mut &raw[..]).ok())
+        .unwrap_or_else(<Module<T>>::block_number)
+}
+
+/// Prefix of the offchain local storage keys holding the next nonce of each account.
+const NONCE_KEY_PREFIX: &[u8] = b"submitter::nonce";
+
+pub fn nonce_key<T: Trait>(account: &T::AccountId) -> Vec<u8> {
+    (NONCE_KEY_PREFIX, account).encode()
+}
+
+/// Number of blocks the on-chain nonce of an account may stay behind its local counter without
+/// moving before the transactions in between are given up on.
+///
+/// A transaction that can no longer be included is dropped by the pool when it revalidates
+/// after the next block import, so its nonce is lost for good. Giving up on a transaction
+/// that is merely late only makes the next submission reuse its nonce and be refused by the
+/// pool, which releases the nonce again.
+pub const NONCE_EXPIRY: u32 = 10;
+
+/// The local nonce counter of an account: the next nonce, the on-chain nonce when the counter
+/// got ahead of it, and the block since which the on-chain nonce has not moved.
+type Counter<T> = (<T as Trait>::Index, <T as Trait>::Index, <T as Trait>::BlockNumber);
+
+/// The nonce to use for the next transaction of `account`.
+///
+/// Transactions submitted from this node but not yet included in a block are tracked in
+/// offchain local storage, so several of them can be submitted per block. The on-chain
+/// nonce wins as soon as it catches up with (or overtakes) the local counter, or once it has
+/// not moved for `NONCE_EXPIRY` blocks: one of the transactions in between was then dropped,
+/// and waiting for its nonce would hold back every later transaction. The expiry runs from the
+/// oldest reservation the chain has not caught up with, so further submissions do not delay it.
+pub fn next_nonce<T: Trait>(account: &T::AccountId) -> T::Index {
+    let raw = sp_io::offchain::local_storage_get(StorageKind::PERSISTENT, &nonce_key::<T>(account));
+    next_nonce_from::<T>(account, &raw)
+}
+
+/// Same as `next_nonce`, given the raw local counter of `account`.
+fn next_nonce_from<T: Trait>(account: &T::AccountId, raw: &Option<Vec<u8>>) -> T::Index {
+    let on_chain = <Module<T>>::account_nonce(account);
+    match decode_counter::<T>(raw) {
+        Some((pending, base, since)) if pending > on_chain && !expired::<T>(on_chain, base, since) => pending,
+        _ => on_chain,
+    }
+}
+
+fn decode_counter<T: Trait>(raw: &Option<Vec<u8>>) -> Option<Counter<T>> {
+    raw.as_ref().and_then(|raw| <Counter<T>>::decode(&mut &raw[..]).ok())
+}
+
+/// Whether the on-chain nonce is still `base`, and has been since `since` for `NONCE_EXPIRY`
+/// blocks.
+fn expired<T: Trait>(on_chain: T::Index, base: T::Index, since: T::BlockNumber) -> bool {
+    on_chain == base && offchain_block::<T>() >= since.saturating_add(NONCE_EXPIRY.into())
+}
+
+/// Reserve the next nonce of `account`, returning it with the raw counter written.
+///
+/// Workers for consecutive blocks may run concurrently, so the counter is only updated if
+/// no other worker changed it since it was read.
+fn reserve_nonce<T: Trait>(account: &T::AccountId) -> (T::Index, Vec<u8>) {
+    let key = nonce_key::<T>(account);
+    loop {
+        let old = sp_io::offchain::local_storage_get(StorageKind::PERSISTENT, &key);
+        let nonce = next_nonce_from::<T>(account, &old);
+        let on_chain = <Module<T>>::account_nonce(account);
+        // Keep measuring from the oldest reservation ahead of a chain that has not moved.
+        let (base, since) = match decode_counter::<T>(&old) {
+            Some((_, base, since)) if nonce > on_chain && base == on_chain => (base, since),
+            _ => (on_chain, offchain_block::<T>()),
+        };
+        let reserved = (nonce + One::one(), base, since).encode();
+        if sp_io::offchain::local_storage_compare_and_set(StorageKind::PERSISTENT, &key, old, &reserved) {
+            return (nonce, reserved);
+        }
+    }
+}
+
+/// Give back a nonce whose transaction could not be submitted, unless another worker
+/// reserved the next one in the meantime.
+fn release_nonce<T: Trait>(account: &T::AccountId, nonce: T::Index, reserved: Vec<u8>) {
+    let released = match decode_counter::<T>(&Some(reserved.clone())) {
+        Some((_, base, since)) => (nonce, base, since).encode(),
+        None => return,
+    };
+    sp_io::offchain::local_storage_compare_and_set(
+        StorageKind::PERSISTENT,
+        &nonce_key::<T>(account),
+        Some(reserved),
+        &released,
+    );
+}
+
+/// Reasons why signing and submitting a transaction can fail.
+#[derive(RuntimeDebug, Clone, Copy, PartialEq, Eq)]
+pub enum SubmitError {
+    /// None of the local keys belongs to an account allowed to submit.
+    NoMatchingKey,
+    /// The payload could not be created or signed with the local key.
+    Signing,
+    /// The extrinsic could not be constructed from the signed payload.
+    Construction,
+    /// The transaction pool rejected the extrinsic.
+    Pool,
+}
+
 /// A utility trait to easily create signed transactions
 /// from accounts in node's local keystore.
 ///
@@ -19,10 +148,84 @@ pub trait SubmitAndSignTransaction<T: Trait, Call> {
     /// A `SubmitSignedTransaction` implementation.
     type SignAndSubmit: SubmitSignedTransaction<T, Call>;
 
+    /// The application key type the local keys are looked up in the keystore with.
+    type LocalKey: RuntimeAppPublic;
+
     fn get_local_keys() -> Vec<(T::AccountId, PublicOf<T, Call, Self::SignAndSubmit>)>;
 
-    fn sign_and_submit(call: impl Into<Call>, public: PublicOf<T, Call, Self::SignAndSubmit>) -> Result<(), ()> {
-        Self::SignAndSubmit::sign_and_submit(call, public)
+    /// Sign the call with the given key and submit it to the transaction pool.
+    ///
+    /// Unlike `SubmitSignedTransaction::sign_and_submit` this reports which step failed, and
+    /// takes the nonce from `next_nonce` so it can be called several times per block.
+    fn sign_and_submit(
+        call: impl Into<Call>,
+        public: PublicOf<T, Call, Self::SignAndSubmit>,
+    ) -> Result<(), SubmitError> {
+        let call = call.into();
+        let id = public.clone().into_account();
+        let (nonce, reserved) = reserve_nonce::<T>(&id);
+        let submit = || -> Result<(), SubmitError> {
+            let (call, signature_data) =
+                <Self::SignAndSubmit as SubmitSignedTransaction<T, Call>>::CreateTransaction::create_transaction::<
+                    <Self::SignAndSubmit as SubmitSignedTransaction<T, Call>>::Signer,
+                >(call, public, id.clone(), nonce)
+                .ok_or(SubmitError::Signing)?;
+            let xt = <Self::SignAndSubmit as SubmitSignedTransaction<T, Call>>::Extrinsic::new(call, Some(signature_data))
+                .ok_or(SubmitError::Construction)?;
+            sp_io::offchain::submit_transaction(xt.encode()).map_err(|_| SubmitError::Pool)
+        };
+        let result = submit();
+        if result.is_err() {
+            release_nonce::<T>(&id, nonce, reserved);
+        }
+        result
+    }
+
+    /// Sign and submit the call once with every local key whose account is in `accounts`.
+    ///
+    /// Returns the result of each submission together with the account it was made for.
+    fn sign_and_submit_all(
+        call: impl Into<Call> + Clone,
+        accounts: &[T::AccountId],
+    ) -> Vec<(T::AccountId, Result<(), SubmitError>)> {
+        Self::get_local_keys()
+            .into_iter()
+            .filter(|(account, _)| accounts.contains(account))
+            .map(|(account, public)| (account, Self::sign_and_submit(call.clone(), public)))
+            .collect()
+    }
+
+    /// Sign `payload` with every local key whose account is in `accounts`, and submit the
+    /// call built from the payload and each signature as an unsigned transaction.
+    ///
+    /// The signature is left for the module's `ValidateUnsigned` to check.
+    fn sign_payload_and_submit_unsigned<P, F>(
+        payload: P,
+        accounts: &[T::AccountId],
+        build_call: F,
+    ) -> Vec<(T::AccountId, Result<(), SubmitError>)>
+    where
+        P: Encode + Clone,
+        F: Fn(P, SignatureOf<T, Call, Self::SignAndSubmit>) -> Call,
+    {
+        Self::get_local_keys()
+            .into_iter()
+            .filter(|(account, _)| accounts.contains(account))
+            .map(|(account, public)| {
+                let res = <<Self::SignAndSubmit as SubmitSignedTransaction<T, Call>>::Signer as Signer<
+                    PublicOf<T, Call, Self::SignAndSubmit>,
+                    SignatureOf<T, Call, Self::SignAndSubmit>,
+                >>::sign(public, &payload)
+                .ok_or(SubmitError::Signing)
+                .and_then(|signature| {
+                    let call = build_call(payload.clone(), signature);
+                    <Self::SignAndSubmit as SubmitSignedTransaction<T, Call>>::Extrinsic::new(call, None)
+                        .ok_or(SubmitError::Construction)
+                })
+                .and_then(|xt| sp_io::offchain::submit_transaction(xt.encode()).map_err(|_| SubmitError::Pool));
+                (account, res)
+            })
+            .collect()
     }
 }
 
@@ -76,6 +279,7 @@ where
     Self: SubmitSignedTransaction<T, Call, Signer = S, Extrinsic = E, CreateTransaction = C>,
 {
     type SignAndSubmit = Self;
+    type LocalKey = S;
 
     fn get_local_keys() -> Vec<(T::AccountId, PublicOf<T, Call, Self::SignAndSubmit>)> {
         // Convert app-specific keys into generic ones.
@@ -199,3 +199,30 @@ impl Verify for UintAuthorityId {
 		true
 	}
 }
+
+/// Real sr25519 `reqs` keys, whose signatures actually verify.
+///
+/// The keys live in a keystore registered as an extension, see `sr25519_keystore`.
+pub type Sr25519AuthorityId = crate::example::crypto::sr25519::Public;
+
+/// A keystore holding a `reqs` key for each deterministic seed (e.g. `"//Alice"`), together
+/// with the public keys, in seed order.
+pub fn sr25519_keystore(seeds: &[&str]) -> (sp_core::traits::BareCryptoStorePtr, Vec<sp_core::sr25519::Public>) {
+	let keystore = sp_core::testing::KeyStore::new();
+	let keys = seeds.iter()
+		.map(|seed| keystore.write().sr25519_generate_new(crate::example::crypto::KEY_TYPE, Some(*seed)).unwrap())
+		.collect();
+	(keystore, keys)
+}
+
+/// Real ed25519 `reqs` keys, see `Sr25519AuthorityId`.
+pub type Ed25519AuthorityId = crate::example::crypto::ed25519::Public;
+
+/// Same as `sr25519_keystore`, with ed25519 keys.
+pub fn ed25519_keystore(seeds: &[&str]) -> (sp_core::traits::BareCryptoStorePtr, Vec<sp_core::ed25519::Public>) {
+	let keystore = sp_core::testing::KeyStore::new();
+	let keys = seeds.iter()
+		.map(|seed| keystore.write().ed25519_generate_new(crate::example::crypto::KEY_TYPE, Some(*seed)).unwrap())
+		.collect();
+	(keystore, keys)
+}
@@ -1,29 +1,56 @@
 use super::*;
 
 use codec::Decode;
-use sp_core::offchain::{
-    testing::{TestOffchainExt, TestTransactionPoolExt, PoolState},
-    OffchainExt, TransactionPoolExt,
-};
-use sp_runtime::traits::Dispatchable;
+use frame_support::dispatch::DispatchResult;
+use sp_core::H256;
+use system::EventRecord;
+use sp_core::offchain::{OffchainExt, TransactionPoolExt};
+use sp_runtime::traits::{Dispatchable, Header as _, OffchainWorker, OnFinalize, OnInitialize};
 use std::sync::Arc;
 
-pub fn exec_with_offchain() -> (sp_io::TestExternalities, Arc<parking_lot::RwLock<PoolState>>) {
+/// Externalities with offchain and transaction pool extensions, returned together with the
+/// pool state, used to script rejections, and the offchain state used to script HTTP responses.
+pub fn exec_with_offchain() -> (
+    sp_io::TestExternalities,
+    Arc<parking_lot::RwLock<MockPoolState>>,
+    Arc<parking_lot::RwLock<MockOffchainState>>,
+) {
     // What authorities will be available during tests
     let local_keys = vec![42.into()];
-    let mut ext = new_test_ext(local_keys);
-    let (offchain, _) = TestOffchainExt::new();
-    let (pool, state) = TestTransactionPoolExt::new();
+    exec_with_authorities(local_keys.clone(), local_keys)
+}
+
+/// Same as `exec_with_offchain`, but with a custom authority set and local keystore.
+pub fn exec_with_authorities(
+    authorities: Vec<UintAuthorityId>,
+    local_keys: Vec<UintAuthorityId>,
+) -> (
+    sp_io::TestExternalities,
+    Arc<parking_lot::RwLock<MockPoolState>>,
+    Arc<parking_lot::RwLock<MockOffchainState>>,
+) {
+    let mut ext = new_test_ext(authorities, local_keys);
+    let (offchain, offchain_state) = MockOffchainExt::new();
+    let (pool, state) = MockPoolExt::new();
     ext.register_extension(OffchainExt::new(offchain));
     ext.register_extension(TransactionPoolExt::new(pool));
-    (ext, state)
+    (ext, state, offchain_state)
+}
+
+pub fn new_test_ext(authorities: Vec<UintAuthorityId>, local_keys: Vec<UintAuthorityId>) -> sp_io::TestExternalities {
+    let config = crate::example::GenesisConfig::<TestRuntime> { authorities, ..Default::default() };
+    new_test_ext_with_genesis(config, local_keys)
 }
 
-pub fn new_test_ext(local_keys: Vec<UintAuthorityId>) -> sp_io::TestExternalities {
-    UintAuthorityId::set_all_keys(local_keys.clone());
+/// Same as `new_test_ext`, with a custom genesis for the example module.
+pub fn new_test_ext_with_genesis(
+    config: crate::example::GenesisConfig<TestRuntime>,
+    local_keys: Vec<UintAuthorityId>,
+) -> sp_io::TestExternalities {
+    UintAuthorityId::set_all_keys(local_keys);
 
     let mut t = system::GenesisConfig::default().build_storage::<TestRuntime>().unwrap();
-    crate::example::GenesisConfig::<TestRuntime> { authorities: local_keys }.assimilate_storage(&mut t).unwrap();
+    config.assimilate_storage(&mut t).unwrap();
     t.into()
 }
 
@@ -31,29 +58,115 @@ pub fn new_test_ext(local_keys: Vec<UintAuthorityId>) -> sp_io::TestExternalitie
 /// analogous to `finalize_block`).
 ///
 /// This function increments the block number and simulates what we have written in
-/// `decl_module` as `fn offchain_worker(_now: T::BlockNumber)`: run the offchain logic if the
-/// current node is an authority.
+/// `decl_module` as `fn offchain_worker(now: T::BlockNumber)`: run the offchain logic and
+/// return `None` if the current node is not expected to answer any pending request.
 ///
 /// Also, since the offchain code might submit some transactions, it queries the transaction
-/// queue and dispatches any submitted transaction. This is also needed because it is a
-/// non-runtime logic (transaction queue) which needs to mocked inside a runtime test.
-pub fn seal_block(state: Arc<parking_lot::RwLock<PoolState>>) -> Option<usize> {
-    let block = System::block_number();
-    System::set_block_number(block + 1);
-    if let Some(_) = Example::authority_id() {
-        // Run offchain logic
-        Example::offchain();
+/// queue and dispatches any submitted transaction, in submission order. This is also needed
+/// because it is a non-runtime logic (transaction queue) which needs to mocked inside a
+/// runtime test. Failing transactions do not abort the test, their outcome is returned.
+pub fn seal_block(state: Arc<parking_lot::RwLock<MockPoolState>>) -> Option<Vec<Dispatched>> {
+    let block = System::block_number() + 1;
+    System::set_block_number(block);
+    // Run offchain logic
+    if Example::offchain(block).is_ok() {
         // if there are any txs submitted to the queue, dispatch them
-        let transactions = &mut state.write().transactions;
-        let count = transactions.len();
-        while let Some(t) = transactions.pop() {
-            let e: Extrinsic = Decode::decode(&mut &*t).unwrap();
-            let (who, _) = e.0.expect("Invalid transaction origin");
-            let call = e.1;
-            let _ = call.dispatch(Origin::signed(who.into())).unwrap();
-        }
-        Some(count)
+        let transactions = state.write().transactions.drain(..).collect::<Vec<_>>();
+        Some(transactions.iter().map(|t| dispatch_transaction(t)).collect())
     } else {
         None
     }
 }
+
+/// A chain of full blocks built on top of the current block, see `Chain::run_blocks`.
+///
+/// `System::finalize` clears the block number, so the head is tracked here, from the header
+/// each block is finalized with.
+pub struct Chain {
+    number: u64,
+    hash: H256,
+    state: Arc<parking_lot::RwLock<MockPoolState>>,
+}
+
+impl Chain {
+    /// Start a chain at the current block, including the transactions submitted to `state`.
+    pub fn new(state: Arc<parking_lot::RwLock<MockPoolState>>) -> Self {
+        let number = System::block_number();
+        Chain { number, hash: System::block_hash(number), state }
+    }
+
+    /// The number of the last block produced.
+    pub fn head(&self) -> u64 {
+        self.number
+    }
+
+    /// Produce `n` full blocks on top of the head, returning the transactions dispatched in
+    /// each of them.
+    ///
+    /// Unlike `seal_block`, every block goes through `System::initialize`, the module hooks and
+    /// `System::finalize`, like `Executive` does. The transactions submitted by the offchain
+    /// worker of a block are included in the next one, as on a real node where offchain workers
+    /// run after import.
+    pub fn run_blocks(&mut self, n: u64) -> Vec<Vec<Dispatched>> {
+        (0..n).map(|_| {
+            let block = self.number + 1;
+            System::initialize(&block, &self.hash, &Default::default(), &Default::default());
+            Example::on_initialize(block);
+
+            let transactions = self.state.write().transactions.drain(..).collect::<Vec<_>>();
+            let dispatched = transactions.iter().map(|t| dispatch_transaction(t)).collect();
+
+            Example::on_finalize(block);
+            let header = System::finalize();
+            self.number = *header.number();
+            self.hash = header.hash();
+
+            Example::offchain_worker(self.number);
+            dispatched
+        }).collect()
+    }
+}
+
+/// The outcome of dispatching a transaction taken from the pool.
+#[derive(Debug, PartialEq)]
+pub struct Dispatched {
+    pub call: Call,
+    /// The signer of the transaction, `None` if it was unsigned.
+    pub origin: Option<UintAuthorityId>,
+    pub result: DispatchResult,
+    /// The events deposited while dispatching.
+    pub events: Vec<EventRecord<<TestRuntime as system::Trait>::Event, H256>>,
+}
+
+/// Decode a transaction taken from the pool and dispatch it with the origin it was signed with.
+pub fn dispatch_transaction(transaction: &[u8]) -> Dispatched {
+    let e: Extrinsic = Decode::decode(&mut &*transaction).unwrap();
+    let signer: Option<UintAuthorityId> = e.0.map(|(who, _)| who.into());
+    let origin = match signer.clone() {
+        Some(who) => Origin::signed(who),
+        None => system::RawOrigin::None.into(),
+    };
+    let before = System::events().len();
+    let result = e.1.clone().dispatch(origin);
+    let events = System::events().into_iter().skip(before).collect();
+    Dispatched { call: e.1, origin: signer, result, events }
+}
+
+/// The events deposited so far, oldest first.
+///
+/// Note that the system module does not record events at block zero.
+pub fn events() -> Vec<TestEvent> {
+    System::events().into_iter().map(|record| record.event).collect()
+}
+
+/// Forget the events deposited so far.
+pub fn clear_events() {
+    System::reset_events();
+}
+
+/// Decode the transactions submitted to the pool and not dispatched yet, oldest first.
+pub fn pool_transactions(state: &Arc<parking_lot::RwLock<MockPoolState>>) -> Vec<Extrinsic> {
+    state.read().transactions.iter()
+        .map(|t| Decode::decode(&mut &**t).unwrap())
+        .collect()
+}
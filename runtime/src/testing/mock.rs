@@ -2,12 +2,14 @@ use crate::submitter::TransactionSubmitter;
 use crate::testing::{authority::UintAuthorityId, types::*};
 use crate::example;
 
+use codec::{Encode, Decode};
 use sp_core::H256;
 use sp_runtime::{
     testing::{Header, TestXt},
-    traits::{IdentityLookup, BlakeTwo256, Verify},
+    traits::{IdentityLookup, BlakeTwo256, SignedExtension, Verify},
+    transaction_validity::{InvalidTransaction, TransactionValidity, TransactionValidityError},
 };
-use frame_support::{impl_outer_origin, impl_outer_dispatch};
+use frame_support::{impl_outer_origin, impl_outer_dispatch, impl_outer_event, weights::DispatchInfo};
 
 pub type Extrinsic = TestXt<Call, ()>;
 pub type SubmitTransaction = TransactionSubmitter<UintAuthorityId, Call, Extrinsic>;
@@ -40,7 +42,7 @@ impl system::Trait for TestRuntime {
     type AccountId = UintAuthorityId;
     type Lookup = IdentityLookup<Self::AccountId>;
     type Header = Header;
-    type Event = ();
+    type Event = TestEvent;
     type BlockHashCount = BlockHashCount;
     type MaximumBlockWeight = MaximumBlockWeight;
     type MaximumBlockLength = MaximumBlockLength;
@@ -50,15 +52,38 @@ impl system::Trait for TestRuntime {
 }
 
 impl example::Trait for TestRuntime {
-    type Event = ();
+    type Event = TestEvent;
     type Call = Call;
+    type AuthorityId = UintAuthorityId;
     type SubmitTransaction = SubmitTransaction;
+    type GracePeriod = GracePeriod;
+    type SubmitWithAllKeys = SubmitWithAllKeys;
+    type Currency = Balances;
+}
+
+impl balances::Trait for TestRuntime {
+    type Balance = Balance;
+    type OnFreeBalanceZero = ();
+    type OnNewAccount = ();
+    type Event = TestEvent;
+    type DustRemoval = ();
+    type TransferPayment = ();
+    type ExistentialDeposit = ExistentialDeposit;
+    type TransferFee = TransferFee;
+    type CreationFee = CreationFee;
 }
 
 impl_outer_origin!{
 	pub enum Origin for TestRuntime {}
 }
 
+impl_outer_event! {
+	pub enum TestEvent for TestRuntime {
+		balances<T>,
+		example<T>,
+	}
+}
+
 impl_outer_dispatch! {
 	pub enum Call for TestRuntime where origin: Origin {
 		example::Example,
@@ -67,3 +92,22 @@ impl_outer_dispatch! {
 
 pub type System = system::Module<TestRuntime>;
 pub type Example = example::Module<TestRuntime>;
+pub type Balances = balances::Module<TestRuntime>;
+
+/// A fee payment extension for an account that cannot pay any fee.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, Debug)]
+pub struct NoFunds;
+
+impl SignedExtension for NoFunds {
+    type AccountId = UintAuthorityId;
+    type Call = Call;
+    type AdditionalSigned = ();
+    type DispatchInfo = DispatchInfo;
+    type Pre = ();
+
+    fn additional_signed(&self) -> Result<(), TransactionValidityError> { Ok(()) }
+
+    fn validate(&self, _: &Self::AccountId, _: &Self::Call, _: Self::DispatchInfo, _: usize) -> TransactionValidity {
+        Err(InvalidTransaction::Payment.into())
+    }
+}
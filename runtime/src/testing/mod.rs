@@ -3,9 +3,19 @@
 mod authority;
 mod methods;
 mod mock;
+mod network;
+mod offchain;
+mod pool;
+#[cfg(test)]
+mod proptests;
 mod types;
 
+pub mod signed;
+
 pub use authority::*;
 pub use methods::*;
 pub use mock::*;
+pub use network::*;
+pub use offchain::*;
+pub use pool::*;
 pub use types::*;
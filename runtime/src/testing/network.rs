@@ -0,0 +1,62 @@
+use super::*;
+
+use std::sync::Arc;
+
+/// A simulated node: its keystore and the transactions its offchain worker submitted.
+pub struct Node {
+    pub keys: Vec<UintAuthorityId>,
+    pub pool: Vec<Vec<u8>>,
+}
+
+/// Several nodes running their offchain workers on the same chain state.
+///
+/// Each node has its own keystore and transaction pool. The nodes share the offchain local
+/// storage, which is only a problem for nodes sharing keys.
+pub struct Network {
+    pub ext: sp_io::TestExternalities,
+    pub nodes: Vec<Node>,
+    pub offchain: Arc<parking_lot::RwLock<MockOffchainState>>,
+    pool: Arc<parking_lot::RwLock<MockPoolState>>,
+}
+
+impl Network {
+    /// A network with the given authority set and one node per set of local keys.
+    pub fn new(authorities: Vec<UintAuthorityId>, nodes: Vec<Vec<UintAuthorityId>>) -> Self {
+        let (ext, pool, offchain) = exec_with_authorities(authorities, vec![]);
+        let nodes = nodes.into_iter().map(|keys| Node { keys, pool: vec![] }).collect();
+        Network { ext, nodes, offchain, pool }
+    }
+
+    pub fn execute_with<R>(&mut self, execute: impl FnOnce() -> R) -> R {
+        self.ext.execute_with(execute)
+    }
+
+    /// Run the offchain worker of every node at the next block, then include the transactions
+    /// of all pools in that block, in node order and each distinct transaction only once.
+    ///
+    /// Returns the outcome of every included transaction.
+    pub fn seal_block(&mut self) -> Vec<Dispatched> {
+        let nodes = &mut self.nodes;
+        let pool = self.pool.clone();
+        self.ext.execute_with(|| {
+            let block = System::block_number() + 1;
+            System::set_block_number(block);
+
+            for node in nodes.iter_mut() {
+                UintAuthorityId::set_all_keys(node.keys.clone());
+                let _ = Example::offchain(block);
+                node.pool.extend(pool.write().transactions.drain(..));
+            }
+
+            let mut included: Vec<Vec<u8>> = vec![];
+            for node in nodes.iter_mut() {
+                for transaction in node.pool.drain(..) {
+                    if !included.contains(&transaction) {
+                        included.push(transaction);
+                    }
+                }
+            }
+            included.iter().map(|t| dispatch_transaction(t)).collect()
+        })
+    }
+}
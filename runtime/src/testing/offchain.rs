@@ -0,0 +1,182 @@
+use sp_core::offchain::{
+    self,
+    testing::{OffchainState, PendingRequest, TestOffchainExt},
+    HttpError, HttpRequestId, HttpRequestStatus, OpaqueNetworkState, StorageKind, Timestamp,
+};
+use std::{collections::BTreeMap, sync::Arc};
+
+/// How a mocked HTTP request completes.
+#[derive(Clone, Debug)]
+enum Outcome {
+    Status(u16),
+    Timeout,
+}
+
+/// State shared between the tests and `MockOffchainExt`.
+pub struct MockOffchainState {
+    /// The state of the wrapped `TestOffchainExt`, holding requests and local storage.
+    pub inner: Arc<parking_lot::RwLock<OffchainState>>,
+    /// The offchain clock, in milliseconds since the unix epoch.
+    pub timestamp: u64,
+    /// The seed returned by `random_seed`, if any. Otherwise the wrapped extension picks one.
+    pub random_seed: Option<[u8; 32]>,
+    outcomes: BTreeMap<u16, Outcome>,
+    next_request: u16,
+}
+
+impl MockOffchainState {
+    /// Expect the next HTTP request to be a GET of `url`, answered with `status` and `body`.
+    pub fn expect_get(&mut self, url: &str, status: u16, body: &[u8]) {
+        self.expect(url, Outcome::Status(status), body.to_vec());
+    }
+
+    /// Expect the next HTTP request to be a GET of `url` that never completes before its
+    /// deadline.
+    pub fn expect_timeout(&mut self, url: &str) {
+        self.expect(url, Outcome::Timeout, vec![]);
+    }
+
+    /// Set the offchain clock, in milliseconds since the unix epoch.
+    pub fn set_timestamp(&mut self, timestamp: u64) {
+        self.timestamp = timestamp;
+    }
+
+    /// Move the offchain clock `millis` milliseconds forward.
+    pub fn advance_timestamp(&mut self, millis: u64) {
+        self.timestamp += millis;
+    }
+
+    /// Make `random_seed` return `seed` from now on.
+    pub fn set_random_seed(&mut self, seed: [u8; 32]) {
+        self.random_seed = Some(seed);
+    }
+
+    /// The value stored under `key` in the persistent local storage.
+    pub fn persistent_storage(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.inner.read().persistent_storage.get(b"", key)
+    }
+
+    fn expect(&mut self, url: &str, outcome: Outcome, body: Vec<u8>) {
+        let id = self.next_request;
+        self.next_request += 1;
+        self.inner.write().expect_request(id, PendingRequest {
+            method: "GET".into(),
+            uri: url.into(),
+            response: Some(body),
+            sent: true,
+            ..Default::default()
+        });
+        self.outcomes.insert(id, outcome);
+    }
+}
+
+/// Offchain externalities wrapping `TestOffchainExt`, which always answers HTTP requests
+/// with status 200 and has no clock, so that tests control both, as well as randomness.
+pub struct MockOffchainExt {
+    inner: TestOffchainExt,
+    state: Arc<parking_lot::RwLock<MockOffchainState>>,
+}
+
+impl MockOffchainExt {
+    pub fn new() -> (Self, Arc<parking_lot::RwLock<MockOffchainState>>) {
+        let (inner, inner_state) = TestOffchainExt::new();
+        let state = Arc::new(parking_lot::RwLock::new(MockOffchainState {
+            inner: inner_state,
+            timestamp: 0,
+            random_seed: None,
+            outcomes: BTreeMap::new(),
+            next_request: 0,
+        }));
+        (MockOffchainExt { inner, state: state.clone() }, state)
+    }
+}
+
+impl offchain::Externalities for MockOffchainExt {
+    fn is_validator(&self) -> bool {
+        self.inner.is_validator()
+    }
+
+    fn network_state(&self) -> Result<OpaqueNetworkState, ()> {
+        self.inner.network_state()
+    }
+
+    fn timestamp(&mut self) -> Timestamp {
+        Timestamp::from_unix_millis(self.state.read().timestamp)
+    }
+
+    fn sleep_until(&mut self, deadline: Timestamp) {
+        let mut state = self.state.write();
+        state.timestamp = state.timestamp.max(deadline.unix_millis());
+    }
+
+    fn random_seed(&mut self) -> [u8; 32] {
+        if let Some(seed) = self.state.read().random_seed {
+            return seed;
+        }
+        self.inner.random_seed()
+    }
+
+    fn local_storage_set(&mut self, kind: StorageKind, key: &[u8], value: &[u8]) {
+        self.inner.local_storage_set(kind, key, value)
+    }
+
+    fn local_storage_compare_and_set(
+        &mut self,
+        kind: StorageKind,
+        key: &[u8],
+        old_value: Option<&[u8]>,
+        new_value: &[u8],
+    ) -> bool {
+        self.inner.local_storage_compare_and_set(kind, key, old_value, new_value)
+    }
+
+    fn local_storage_get(&mut self, kind: StorageKind, key: &[u8]) -> Option<Vec<u8>> {
+        self.inner.local_storage_get(kind, key)
+    }
+
+    fn http_request_start(&mut self, method: &str, uri: &str, meta: &[u8]) -> Result<HttpRequestId, ()> {
+        self.inner.http_request_start(method, uri, meta)
+    }
+
+    fn http_request_add_header(&mut self, request_id: HttpRequestId, name: &str, value: &str) -> Result<(), ()> {
+        self.inner.http_request_add_header(request_id, name, value)
+    }
+
+    fn http_request_write_body(
+        &mut self,
+        request_id: HttpRequestId,
+        chunk: &[u8],
+        deadline: Option<Timestamp>,
+    ) -> Result<(), HttpError> {
+        self.inner.http_request_write_body(request_id, chunk, deadline)
+    }
+
+    fn http_response_wait(&mut self, ids: &[HttpRequestId], deadline: Option<Timestamp>) -> Vec<HttpRequestStatus> {
+        let statuses = self.inner.http_response_wait(ids, deadline);
+        let mut state = self.state.write();
+        ids.iter().zip(statuses).map(|(id, status)| match (state.outcomes.get(&id.0).cloned(), status) {
+            (Some(Outcome::Timeout), _) => {
+                // Waiting for a request that never completes takes until the deadline.
+                if let Some(deadline) = deadline {
+                    state.timestamp = state.timestamp.max(deadline.unix_millis());
+                }
+                HttpRequestStatus::DeadlineReached
+            }
+            (Some(Outcome::Status(code)), HttpRequestStatus::Finished(_)) => HttpRequestStatus::Finished(code),
+            (_, status) => status,
+        }).collect()
+    }
+
+    fn http_response_headers(&mut self, request_id: HttpRequestId) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.inner.http_response_headers(request_id)
+    }
+
+    fn http_response_read_body(
+        &mut self,
+        request_id: HttpRequestId,
+        buffer: &mut [u8],
+        deadline: Option<Timestamp>,
+    ) -> Result<usize, HttpError> {
+        self.inner.http_response_read_body(request_id, buffer, deadline)
+    }
+}
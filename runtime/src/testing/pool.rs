@@ -0,0 +1,60 @@
+use sp_core::offchain::TransactionPool;
+use std::{collections::VecDeque, sync::Arc};
+
+/// Why a scripted submission is refused by `MockPoolExt`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rejection {
+    /// The pool has no room left.
+    Full,
+    /// The transaction failed validation.
+    Invalid,
+    /// The same transaction is already in the pool.
+    AlreadyImported,
+}
+
+/// State shared between the tests and `MockPoolExt`.
+#[derive(Default)]
+pub struct MockPoolState {
+    /// The transactions accepted so far, oldest first.
+    pub transactions: Vec<Vec<u8>>,
+    /// The transactions refused so far, oldest first, with the reason.
+    pub rejected: Vec<(Vec<u8>, Rejection)>,
+    rejections: VecDeque<Rejection>,
+}
+
+impl MockPoolState {
+    /// Refuse the next submission, after the ones already scripted to be refused.
+    pub fn reject_next(&mut self, rejection: Rejection) {
+        self.rejections.push_back(rejection);
+    }
+}
+
+/// A transaction pool accepting every submission, except the ones scripted with
+/// `MockPoolState::reject_next`. Unlike `TestTransactionPoolExt`, it lets tests exercise the
+/// submission failures of the offchain worker.
+pub struct MockPoolExt {
+    state: Arc<parking_lot::RwLock<MockPoolState>>,
+}
+
+impl MockPoolExt {
+    pub fn new() -> (Self, Arc<parking_lot::RwLock<MockPoolState>>) {
+        let state = Arc::new(parking_lot::RwLock::new(MockPoolState::default()));
+        (MockPoolExt { state: state.clone() }, state)
+    }
+}
+
+impl TransactionPool for MockPoolExt {
+    fn submit_transaction(&mut self, extrinsic: Vec<u8>) -> Result<(), ()> {
+        let mut state = self.state.write();
+        match state.rejections.pop_front() {
+            Some(rejection) => {
+                state.rejected.push((extrinsic, rejection));
+                Err(())
+            }
+            None => {
+                state.transactions.push(extrinsic);
+                Ok(())
+            }
+        }
+    }
+}
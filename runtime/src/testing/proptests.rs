@@ -0,0 +1,102 @@
+//! Random sequences of calls, authority changes and blocks against `TestRuntime`, checking
+//! the invariants of the example module after every step.
+
+use super::*;
+use crate::example::{Authorities, DataRequests};
+use frame_support::{StorageLinkedMap, StorageValue};
+use proptest::prelude::*;
+use std::sync::Arc;
+
+/// The accounts taking part, the first ones being the initial authorities.
+const ACCOUNTS: u64 = 5;
+
+#[derive(Clone, Debug)]
+enum Op {
+    Request(u64, u64),
+    StoreResult(u64, u64, u64),
+    SetAuthorities(Vec<u64>),
+    SealBlock,
+}
+
+fn account() -> impl Strategy<Value = u64> {
+    1..=ACCOUNTS
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        (account(), any::<u64>()).prop_map(|(who, value)| Op::Request(who, value)),
+        // A few ids past the ones in use, to also try unknown requests.
+        (account(), 0..20u64, any::<u64>()).prop_map(|(who, id, value)| Op::StoreResult(who, id, value)),
+        proptest::collection::vec(account(), 0..=ACCOUNTS as usize).prop_map(Op::SetAuthorities),
+        Just(Op::SealBlock),
+    ]
+}
+
+/// Every request is either pending or answered, and answered exactly once.
+fn check_invariants() -> Result<(), TestCaseError> {
+    let results = Example::results();
+    let mut answered = results.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+    answered.sort();
+    answered.dedup();
+    prop_assert_eq!(answered.len(), results.len(), "a request was answered twice: {:?}", results);
+    for id in &answered {
+        prop_assert!(Example::data_request(*id).is_none(), "answered request #{} still pending", id);
+    }
+    let pending = <DataRequests<TestRuntime>>::enumerate().count() as u64;
+    prop_assert_eq!(pending + results.len() as u64, Example::next_request_id());
+    Ok(())
+}
+
+fn apply(op: Op, state: &Arc<parking_lot::RwLock<MockPoolState>>) -> Result<(), TestCaseError> {
+    let before = Example::results();
+    match op {
+        Op::Request(who, value) => {
+            let id = Example::next_request_id();
+            prop_assert!(Example::request(Origin::signed(who.into()), value).is_ok());
+            prop_assert_eq!(Example::data_request(id).map(|(v, _)| v), Some(value));
+            prop_assert_eq!(Example::results(), before);
+        }
+        Op::StoreResult(who, id, value) => {
+            let allowed = Example::authorities().contains(&UintAuthorityId(who)) && Example::data_request(id).is_some();
+            let result = Example::store_result(Origin::signed(who.into()), id, value);
+            if allowed {
+                prop_assert!(result.is_ok());
+                let mut expected = before;
+                expected.push((id, value));
+                prop_assert_eq!(Example::results(), expected);
+            } else {
+                prop_assert!(result.is_err());
+                prop_assert_eq!(Example::results(), before);
+            }
+        }
+        Op::SetAuthorities(accounts) => {
+            <Authorities<TestRuntime>>::put(accounts.into_iter().map(UintAuthorityId).collect::<Vec<_>>());
+        }
+        Op::SealBlock => {
+            let authorities = Example::authorities();
+            for dispatched in seal_block(state.clone()).unwrap_or_default() {
+                if dispatched.result.is_ok() {
+                    let origin = dispatched.origin.expect("results are signed");
+                    prop_assert!(authorities.contains(&origin), "non-authority {:?} stored a result", origin);
+                }
+            }
+            prop_assert!(Example::results().starts_with(&before), "results were rewritten");
+        }
+    }
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn example_module_invariants(ops in proptest::collection::vec(op(), 1..50)) {
+        let accounts: Vec<UintAuthorityId> = (1..=ACCOUNTS).map(Into::into).collect();
+        let (mut ext, state, _) = exec_with_authorities(accounts[..2].to_vec(), accounts);
+        ext.execute_with(|| -> Result<(), TestCaseError> {
+            for op in ops {
+                apply(op, &state)?;
+                check_invariants()?;
+            }
+            Ok(())
+        })?;
+    }
+}
@@ -0,0 +1,199 @@
+//! Mock runtimes signing with real keys from the keystore, one per `reqs` key scheme.
+
+use frame_support::dispatch::DispatchResult;
+
+/// The outcome of including a `SignedXt` in a block.
+#[derive(Debug, PartialEq)]
+pub enum Included {
+    /// The signature did not verify, so the call was not dispatched.
+    BadSignature,
+    Dispatched(DispatchResult),
+}
+
+/// Declares `SignedTestRuntime` and its helpers for the key scheme `$scheme` of both
+/// `sp_core` and `example::crypto`, with keys from `$keystore`.
+macro_rules! signed_test_runtime {
+    ($scheme:ident, $keystore:ident) => {
+        use crate::example::{self, crypto};
+        use crate::submitter::TransactionSubmitter;
+        use crate::testing::{
+            authority::$keystore, offchain::MockOffchainExt, pool::{MockPoolExt, MockPoolState}, types::*,
+        };
+        use super::Included;
+
+        use codec::{Encode, Decode};
+        use frame_support::{impl_outer_origin, impl_outer_dispatch, impl_outer_event};
+        use sp_core::{
+            offchain::{OffchainExt, TransactionPoolExt},
+            traits::KeystoreExt, H256,
+        };
+        use sp_runtime::{
+            testing::Header,
+            traits::{BlakeTwo256, Dispatchable, IdentityLookup, Verify},
+            BuildStorage,
+        };
+        use std::sync::Arc;
+
+        pub type AccountId = sp_core::$scheme::Public;
+        pub type Signature = sp_core::$scheme::Signature;
+        pub type SubmitTransaction = TransactionSubmitter<crypto::$scheme::Public, Call, SignedXt>;
+
+        /// A mock runtime like `TestRuntime`, but signing with real keys from the keystore.
+        #[derive(Clone, PartialEq, Eq, Debug)]
+        pub struct SignedTestRuntime;
+
+        /// A mock extrinsic carrying a signature of `(call, signer, nonce)`.
+        #[derive(Clone, PartialEq, Eq, Encode, Decode, Debug)]
+        pub struct SignedXt {
+            pub signature: Option<(AccountId, Signature, u64)>,
+            pub call: Call,
+        }
+
+        impl SignedXt {
+            /// Whether the extrinsic is unsigned or signed by the account it claims.
+            pub fn is_valid(&self) -> bool {
+                match &self.signature {
+                    Some((signer, signature, nonce)) => signature.verify(&(&self.call, signer, nonce).encode()[..], signer),
+                    None => true,
+                }
+            }
+        }
+
+        impl sp_runtime::traits::Extrinsic for SignedXt {
+            type Call = Call;
+            type SignaturePayload = (AccountId, Signature, u64);
+
+            fn is_signed(&self) -> Option<bool> {
+                Some(self.signature.is_some())
+            }
+
+            fn new(call: Call, signature: Option<Self::SignaturePayload>) -> Option<Self> {
+                Some(SignedXt { signature, call })
+            }
+        }
+
+        impl system::offchain::CreateTransaction<SignedTestRuntime, SignedXt> for Call {
+            type Public = AccountId;
+            type Signature = Signature;
+
+            fn create_transaction<F: system::offchain::Signer<Self::Public, Self::Signature>>(
+                call: Call,
+                public: Self::Public,
+                account: AccountId,
+                index: u64,
+            ) -> Option<(Call, <SignedXt as sp_runtime::traits::Extrinsic>::SignaturePayload)> {
+                let signature = F::sign(public, &(&call, &account, index))?;
+                Some((call, (account, signature, index)))
+            }
+        }
+
+        impl system::Trait for SignedTestRuntime {
+            type Origin = Origin;
+            type Call = Call;
+            type Index = u64;
+            type BlockNumber = u64;
+            type Hash = H256;
+            type Hashing = BlakeTwo256;
+            type AccountId = AccountId;
+            type Lookup = IdentityLookup<Self::AccountId>;
+            type Header = Header;
+            type Event = SignedTestEvent;
+            type BlockHashCount = BlockHashCount;
+            type MaximumBlockWeight = MaximumBlockWeight;
+            type MaximumBlockLength = MaximumBlockLength;
+            type AvailableBlockRatio = AvailableBlockRatio;
+            type Version = ();
+            type ModuleToIndex = ();
+        }
+
+        impl example::Trait for SignedTestRuntime {
+            type Event = SignedTestEvent;
+            type Call = Call;
+            type AuthorityId = crypto::$scheme::Public;
+            type SubmitTransaction = SubmitTransaction;
+            type GracePeriod = GracePeriod;
+            type SubmitWithAllKeys = SubmitWithAllKeys;
+            type Currency = balances::Module<SignedTestRuntime>;
+        }
+
+        impl balances::Trait for SignedTestRuntime {
+            type Balance = Balance;
+            type OnFreeBalanceZero = ();
+            type OnNewAccount = ();
+            type Event = SignedTestEvent;
+            type DustRemoval = ();
+            type TransferPayment = ();
+            type ExistentialDeposit = ExistentialDeposit;
+            type TransferFee = TransferFee;
+            type CreationFee = CreationFee;
+        }
+
+        impl_outer_origin!{
+            pub enum Origin for SignedTestRuntime {}
+        }
+
+        impl_outer_event! {
+            pub enum SignedTestEvent for SignedTestRuntime {
+                balances<T>,
+                example<T>,
+            }
+        }
+
+        impl_outer_dispatch! {
+            pub enum Call for SignedTestRuntime where origin: Origin {
+                example::Example,
+            }
+        }
+
+        pub type System = system::Module<SignedTestRuntime>;
+        pub type Example = example::Module<SignedTestRuntime>;
+
+        /// Externalities for `SignedTestRuntime` with a `reqs` key in the keystore for each seed
+        /// (e.g. `"//Alice"`). The accounts of these keys are the authorities.
+        pub fn exec_with_keystore(
+            seeds: &[&str],
+        ) -> (sp_io::TestExternalities, Arc<parking_lot::RwLock<MockPoolState>>, Vec<AccountId>) {
+            let (keystore, authorities) = $keystore(seeds);
+            let mut t = system::GenesisConfig::default().build_storage::<SignedTestRuntime>().unwrap();
+            example::GenesisConfig::<SignedTestRuntime> { authorities: authorities.clone(), ..Default::default() }
+                .assimilate_storage(&mut t)
+                .unwrap();
+            let mut ext: sp_io::TestExternalities = t.into();
+            let (offchain, _) = MockOffchainExt::new();
+            let (pool, state) = MockPoolExt::new();
+            ext.register_extension(OffchainExt::new(offchain));
+            ext.register_extension(TransactionPoolExt::new(pool));
+            ext.register_extension(KeystoreExt(keystore));
+            (ext, state, authorities)
+        }
+
+        /// Like `seal_block`, but only dispatches transactions whose signature verifies.
+        pub fn seal_block(state: Arc<parking_lot::RwLock<MockPoolState>>) -> Vec<Included> {
+            let block = System::block_number() + 1;
+            System::set_block_number(block);
+            let _ = Example::offchain(block);
+            let transactions = state.write().transactions.drain(..).collect::<Vec<_>>();
+            transactions.iter().map(|t| {
+                let xt: SignedXt = Decode::decode(&mut &**t).unwrap();
+                if !xt.is_valid() {
+                    return Included::BadSignature;
+                }
+                let origin = match xt.signature {
+                    Some((who, _, _)) => Origin::signed(who),
+                    None => system::RawOrigin::None.into(),
+                };
+                Included::Dispatched(xt.call.dispatch(origin))
+            }).collect()
+        }
+    };
+}
+
+/// `SignedTestRuntime` with sr25519 keys, as used by the node.
+pub mod sr25519 {
+    signed_test_runtime!(sr25519, sr25519_keystore);
+}
+
+/// `SignedTestRuntime` with ed25519 keys.
+pub mod ed25519 {
+    signed_test_runtime!(ed25519, ed25519_keystore);
+}
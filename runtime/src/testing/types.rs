@@ -1,5 +1,6 @@
-use frame_support::{weights::Weight, parameter_types};
+use frame_support::{weights::Weight, parameter_types, traits::Get};
 use sp_runtime::Perbill;
+use std::cell::RefCell;
 
 pub type AccountIndex = u64;
 pub type Balance = u128;
@@ -11,7 +12,36 @@ parameter_types! {
     pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
 }
 
+parameter_types! {
+	pub const ExistentialDeposit: Balance = 0;
+	pub const TransferFee: Balance = 0;
+	pub const CreationFee: Balance = 0;
+}
+
 parameter_types! {
 	pub const TransactionBaseFee: Balance = 0;
 	pub const TransactionByteFee: Balance = 1;
 }
+
+parameter_types! {
+	pub const GracePeriod: u64 = 3;
+}
+
+thread_local! {
+	static SUBMIT_WITH_ALL_KEYS: RefCell<bool> = RefCell::new(false);
+}
+
+/// Whether the offchain worker answers with every local key, switchable from tests.
+pub struct SubmitWithAllKeys;
+
+impl SubmitWithAllKeys {
+	pub fn set(value: bool) {
+		SUBMIT_WITH_ALL_KEYS.with(|v| *v.borrow_mut() = value)
+	}
+}
+
+impl Get<bool> for SubmitWithAllKeys {
+	fn get() -> bool {
+		SUBMIT_WITH_ALL_KEYS.with(|v| *v.borrow())
+	}
+}
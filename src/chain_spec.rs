@@ -1,11 +1,12 @@
 use grandpa_primitives::AuthorityId as GrandpaId;
 use runtime::{
-	AccountId, AuraConfig, BalancesConfig, ExampleConfig, GenesisConfig, GrandpaConfig,
+	AccountId, AuraConfig, Balance, BalancesConfig, ExampleConfig, GenesisConfig, GrandpaConfig,
 	IndicesConfig, Signature, SudoConfig, SystemConfig, WASM_BINARY,
 };
 use sc_service;
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
-use sp_core::{sr25519, Pair, Public};
+use serde::Deserialize;
+use sp_core::{crypto::Ss58Codec, ed25519, sr25519, Pair, Public};
 use sp_runtime::traits::{IdentifyAccount, Verify};
 
 // Note this is the URL for the telemetry server
@@ -23,6 +24,11 @@ pub enum Alternative {
 	Development,
 	/// Whatever the current runtime is, with simple Alice/Bob auths.
 	LocalTestnet,
+	/// Whatever the current runtime is, with Alice/Bob/Charlie/Dave auths, Alice/Bob oracles
+	/// and Eve/Ferdie as plain users.
+	MultiNodeTestnet,
+	/// Whatever the current runtime is, with the chain described in `res/staging.json`.
+	Staging,
 }
 
 /// Helper function to generate a crypto pair from seed
@@ -62,6 +68,7 @@ impl Alternative {
 							get_account_id_from_seed::<sr25519::Public>("Alice"),
 							get_account_id_from_seed::<sr25519::Public>("Bob"),
 						],
+						vec![get_account_id_from_seed::<sr25519::Public>("Alice")],
 						true,
 					)
 				},
@@ -85,6 +92,39 @@ impl Alternative {
 							get_account_id_from_seed::<sr25519::Public>("Alice"),
 							get_account_id_from_seed::<sr25519::Public>("Bob"),
 						],
+						vec![
+							get_account_id_from_seed::<sr25519::Public>("Alice"),
+							get_account_id_from_seed::<sr25519::Public>("Bob"),
+						],
+						true,
+					)
+				},
+				vec![],
+				None,
+				None,
+				None,
+				None,
+			),
+			Alternative::MultiNodeTestnet => ChainSpec::from_genesis(
+				"Multi Node Testnet",
+				"multi_node_testnet",
+				|| {
+					testnet_genesis(
+						vec![
+							get_authority_keys_from_seed("Alice"),
+							get_authority_keys_from_seed("Bob"),
+							get_authority_keys_from_seed("Charlie"),
+							get_authority_keys_from_seed("Dave"),
+						],
+						get_account_id_from_seed::<sr25519::Public>("Alice"),
+						["Alice", "Bob", "Charlie", "Dave", "Eve", "Ferdie"]
+							.iter()
+							.map(|seed| get_account_id_from_seed::<sr25519::Public>(seed))
+							.collect(),
+						vec![
+							get_account_id_from_seed::<sr25519::Public>("Alice"),
+							get_account_id_from_seed::<sr25519::Public>("Bob"),
+						],
 						true,
 					)
 				},
@@ -94,6 +134,7 @@ impl Alternative {
 				None,
 				None,
 			),
+			Alternative::Staging => return ChainDescription::staging().load(),
 		})
 	}
 
@@ -101,6 +142,8 @@ impl Alternative {
 		match s {
 			"dev" => Some(Alternative::Development),
 			"" | "local" => Some(Alternative::LocalTestnet),
+			"multi" => Some(Alternative::MultiNodeTestnet),
+			"staging" => Some(Alternative::Staging),
 			_ => None,
 		}
 	}
@@ -110,7 +153,22 @@ fn testnet_genesis(
 	initial_authorities: Vec<(AuraId, GrandpaId)>,
 	root_key: AccountId,
 	endowed_accounts: Vec<AccountId>,
+	oracle_authorities: Vec<AccountId>,
 	_enable_println: bool,
+) -> GenesisConfig {
+	genesis(
+		initial_authorities,
+		root_key,
+		endowed_accounts.into_iter().map(|k| (k, 1 << 60)).collect(),
+		oracle_authorities,
+	)
+}
+
+fn genesis(
+	initial_authorities: Vec<(AuraId, GrandpaId)>,
+	root_key: AccountId,
+	balances: Vec<(AccountId, Balance)>,
+	oracle_authorities: Vec<AccountId>,
 ) -> GenesisConfig {
 	GenesisConfig {
 		system: Some(SystemConfig {
@@ -118,14 +176,10 @@ fn testnet_genesis(
 			changes_trie_config: Default::default(),
 		}),
 		indices: Some(IndicesConfig {
-			ids: endowed_accounts.clone(),
+			ids: balances.iter().map(|(k, _)| k.clone()).collect(),
 		}),
 		balances: Some(BalancesConfig {
-			balances: endowed_accounts
-				.iter()
-				.cloned()
-				.map(|k| (k, 1 << 60))
-				.collect(),
+			balances,
 			vesting: vec![],
 		}),
 		sudo: Some(SudoConfig {
@@ -141,7 +195,138 @@ fn testnet_genesis(
 				.collect(),
 		}),
 		example: Some(ExampleConfig {
-			authorities: endowed_accounts.clone(),
+			authorities: oracle_authorities,
+			..Default::default()
 		}),
 	}
 }
+
+/// A short description of a chain, from which `generate-spec` builds a full chain spec.
+///
+/// Accounts are given either as SS58 addresses or as secret key URIs such as `//Alice`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ChainDescription {
+	/// The human readable name of the chain.
+	pub name: String,
+	/// The id of the chain, also naming its directory under the base path.
+	pub id: String,
+	/// The id of the network protocol, so that nodes of different chains do not connect.
+	#[serde(default)]
+	pub protocol_id: Option<String>,
+	/// The Aura and GRANDPA authorities.
+	pub authorities: Vec<AuthorityDescription>,
+	/// The oracle authorities.
+	pub oracles: Vec<String>,
+	/// The endowed accounts with their free balance.
+	pub balances: Vec<(String, BalanceDescription)>,
+	/// The sudo key.
+	pub sudo: String,
+}
+
+/// A balance in a `ChainDescription`, as a JSON number or, for amounts beyond `u64`, a
+/// decimal string.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum BalanceDescription {
+	/// A balance fitting in a JSON number.
+	Number(u64),
+	/// A balance written as a decimal string, e.g. `"1000000000000000000000"`.
+	Text(String),
+}
+
+impl BalanceDescription {
+	fn balance(&self) -> Result<Balance, String> {
+		match self {
+			BalanceDescription::Number(balance) => Ok(Balance::from(*balance)),
+			BalanceDescription::Text(balance) => balance.parse().map_err(|e| format!("Invalid balance {:?}: {}", balance, e)),
+		}
+	}
+}
+
+/// The keys of a block authority in a `ChainDescription`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum AuthorityDescription {
+	/// A secret key URI both keys are derived from.
+	Seed(String),
+	/// The SS58 addresses of the Aura (sr25519) and GRANDPA (ed25519) keys.
+	Keys {
+		/// The Aura key.
+		aura: String,
+		/// The GRANDPA key.
+		grandpa: String,
+	},
+}
+
+impl AuthorityDescription {
+	fn keys(&self) -> Result<(AuraId, GrandpaId), String> {
+		Ok(match self {
+			AuthorityDescription::Seed(suri) => (
+				sr25519::Pair::from_string(suri, None)
+					.map_err(|e| format!("Invalid authority seed {:?}: {:?}", suri, e))?
+					.public()
+					.into(),
+				ed25519::Pair::from_string(suri, None)
+					.map_err(|e| format!("Invalid authority seed {:?}: {:?}", suri, e))?
+					.public()
+					.into(),
+			),
+			AuthorityDescription::Keys { aura, grandpa } => (
+				sr25519::Public::from_ss58check(aura)
+					.map_err(|e| format!("Invalid Aura key {:?}: {:?}", aura, e))?
+					.into(),
+				ed25519::Public::from_ss58check(grandpa)
+					.map_err(|e| format!("Invalid GRANDPA key {:?}: {:?}", grandpa, e))?
+					.into(),
+			),
+		})
+	}
+}
+
+/// Parse an account given as an SS58 address or a secret key URI.
+fn parse_account(account: &str) -> Result<AccountId, String> {
+	if let Ok(id) = AccountId::from_ss58check(account) {
+		return Ok(id);
+	}
+	let pair = sr25519::Pair::from_string(account, None)
+		.map_err(|e| format!("Invalid account {:?}: {:?}", account, e))?;
+	Ok(AccountPublic::from(pair.public()).into_account())
+}
+
+impl ChainDescription {
+	/// Parse a description from JSON.
+	pub fn from_json(json: &[u8]) -> Result<Self, String> {
+		serde_json::from_slice(json).map_err(|e| format!("Invalid chain description: {}", e))
+	}
+
+	/// The description of the staging preset.
+	pub fn staging() -> Self {
+		Self::from_json(include_bytes!("../res/staging.json")).expect("the staging description is valid; qed")
+	}
+
+	/// Build the chain spec, checking every key and account first.
+	pub fn load(self) -> Result<ChainSpec, String> {
+		let authorities = self.authorities.iter().map(AuthorityDescription::keys).collect::<Result<Vec<_>, _>>()?;
+		let root_key = parse_account(&self.sudo)?;
+		let balances = self.balances
+			.iter()
+			.map(|(account, balance)| Ok((parse_account(account)?, balance.balance()?)))
+			.collect::<Result<Vec<_>, String>>()?;
+		let oracles = self.oracles.iter().map(|account| parse_account(account)).collect::<Result<Vec<_>, _>>()?;
+		if authorities.is_empty() {
+			return Err("A chain needs at least one authority".into());
+		}
+
+		Ok(ChainSpec::from_genesis(
+			&self.name,
+			&self.id,
+			move || genesis(authorities.clone(), root_key.clone(), balances.clone(), oracles.clone()),
+			vec![],
+			None,
+			self.protocol_id.as_deref(),
+			None,
+			None,
+		))
+	}
+}
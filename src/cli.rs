@@ -6,14 +6,224 @@ use futures::{
 	future::{select, Map},
 	FutureExt, TryFutureExt,
 };
+use codec::Encode;
 use log::info;
-use sc_cli::{display_role, informant, parse_and_prepare, NoCustom, ParseAndPrepare};
+use sc_cli::{display_role, informant, parse_and_prepare, GetSharedParams, NoCustom, ParseAndPrepare, SharedParams};
 pub use sc_cli::{error, IntoExit, VersionInfo};
 use sc_service::{AbstractService, Configuration, Roles as ServiceRoles};
 use sp_consensus_aura::sr25519::AuthorityPair as AuraPair;
-use std::cell::RefCell;
+use sp_core::{crypto::Ss58Codec, hexdisplay::HexDisplay, sr25519, Pair};
+use sp_runtime::{app_crypto::AppKey, generic::Era, traits::IdentifyAccount, MultiSigner};
+use std::{cell::RefCell, path::PathBuf};
+use structopt::StructOpt;
 use tokio::runtime::Runtime;
 
+/// Subcommands specific to this node.
+#[derive(Debug, Clone, StructOpt)]
+pub enum CustomSubcommands {
+	/// Sign an `example::request` extrinsic offline and print it hex-encoded.
+	#[structopt(name = "sign-request")]
+	SignRequest(SignRequestCmd),
+
+	/// Insert an oracle (`reqs`) key into the keystore of the node, without starting it.
+	#[structopt(name = "insert-oracle-key")]
+	InsertOracleKey(InsertOracleKeyCmd),
+
+	/// Generate a chain spec from a short JSON description of the chain.
+	#[structopt(name = "generate-spec")]
+	GenerateSpec(GenerateSpecCmd),
+}
+
+impl GetSharedParams for CustomSubcommands {
+	fn shared_params(&self) -> Option<&SharedParams> {
+		match self {
+			CustomSubcommands::SignRequest(_) | CustomSubcommands::GenerateSpec(_) => None,
+			CustomSubcommands::InsertOracleKey(cmd) => Some(&cmd.shared_params),
+		}
+	}
+}
+
+/// The `sign-request` command.
+#[derive(Debug, Clone, StructOpt)]
+pub struct SignRequestCmd {
+	/// The secret key URI of the signer, e.g. `//Alice`.
+	#[structopt(long)]
+	suri: String,
+
+	/// The password of the secret key URI, if any.
+	#[structopt(long)]
+	password: Option<String>,
+
+	/// The nonce of the signer account.
+	#[structopt(long)]
+	nonce: runtime::Index,
+
+	/// The hash of the genesis block of the chain the extrinsic is for, hex-encoded.
+	#[structopt(long)]
+	genesis_hash: String,
+
+	/// The value to request.
+	#[structopt(long)]
+	value: u64,
+
+	/// The tip paid to the block author.
+	#[structopt(long, default_value = "0")]
+	tip: runtime::Balance,
+}
+
+/// Node options specific to this node.
+#[derive(Debug, Clone, Default, StructOpt)]
+pub struct NodeParams {
+	/// Insert the oracle (`reqs`) key of this secret key URI into the keystore at startup,
+	/// e.g. `//Alice`.
+	#[structopt(long = "oracle-key-seed", conflicts_with = "oracle-key-file")]
+	oracle_key_seed: Option<String>,
+
+	/// Like `--oracle-key-seed`, with the secret key URI read from a file.
+	#[structopt(long = "oracle-key-file", parse(from_os_str))]
+	oracle_key_file: Option<PathBuf>,
+
+	/// Seal a block as soon as a transaction enters the pool, instead of running Aura and
+	/// GRANDPA. Meant for single node development chains.
+	#[structopt(long = "instant-seal")]
+	instant_seal: bool,
+}
+
+sc_cli::impl_augment_clap!(NodeParams);
+
+impl NodeParams {
+	/// The secret key URI of the oracle key to insert, if any.
+	fn suri(&self) -> Result<Option<String>, String> {
+		match (&self.oracle_key_seed, &self.oracle_key_file) {
+			(Some(suri), _) => Ok(Some(suri.clone())),
+			(None, Some(file)) => read_suri(file).map(Some),
+			(None, None) => Ok(None),
+		}
+	}
+}
+
+/// Read a secret key URI from a file, ignoring surrounding whitespace.
+fn read_suri(file: &std::path::Path) -> Result<String, String> {
+	std::fs::read_to_string(file)
+		.map(|suri| suri.trim().to_string())
+		.map_err(|e| format!("Cannot read {}: {}", file.display(), e))
+}
+
+/// The `insert-oracle-key` command.
+#[derive(Debug, Clone, StructOpt)]
+pub struct InsertOracleKeyCmd {
+	/// The secret key URI of the oracle key, e.g. `//Alice`.
+	#[structopt(long, conflicts_with = "file", required_unless = "file")]
+	suri: Option<String>,
+
+	/// A file holding the secret key URI of the oracle key.
+	#[structopt(long, parse(from_os_str))]
+	file: Option<PathBuf>,
+
+	/// The password of the keystore, if any.
+	#[structopt(long)]
+	password: Option<String>,
+
+	/// The keystore to insert the key into, if the node runs with `--keystore-path`.
+	#[structopt(long = "keystore-path", parse(from_os_str))]
+	keystore_path: Option<PathBuf>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	shared_params: SharedParams,
+}
+
+impl InsertOracleKeyCmd {
+	/// Insert the key into `--keystore-path`, or else `<base path>/chains/<chain id>/keystore`,
+	/// where the node looks for it.
+	fn run(self, version: &VersionInfo) -> error::Result<()> {
+		let suri = match (&self.suri, &self.file) {
+			(Some(suri), _) => suri.clone(),
+			(None, Some(file)) => read_suri(file)?,
+			(None, None) => return Err("Either --suri or --file is required".to_string().into()),
+		};
+
+		let path = match &self.keystore_path {
+			Some(path) => path.clone(),
+			None => self.default_keystore_path(version)?,
+		};
+		let keystore = sc_keystore::Store::open(path.clone(), self.password.map(Into::into))
+			.map_err(|e| format!("Cannot open the keystore at {}: {}", path.display(), e))?;
+		let pair = keystore.write()
+			.insert_by_type::<<service::OracleId as AppKey>::Pair>(runtime::example::crypto::KEY_TYPE, &suri)
+			.map_err(|e| format!("Cannot insert the key: {}", e))?;
+		println!("Inserted oracle key {} into {}", pair.public().to_ss58check(), path.display());
+		Ok(())
+	}
+
+	/// The keystore of the chain selected by the shared parameters, under the base path.
+	fn default_keystore_path(&self, version: &VersionInfo) -> error::Result<PathBuf> {
+		// Like sc-cli, `--chain` wins over `--dev`.
+		let chain = match &self.shared_params.chain {
+			Some(chain) => chain.as_str(),
+			None if self.shared_params.dev => "dev",
+			None => "",
+		};
+		let spec = load_spec(chain)?.ok_or_else(|| format!("Unknown chain: {:?}", chain))?;
+		let base_path = match &self.shared_params.base_path {
+			Some(path) => path.clone(),
+			None => app_dirs::get_app_root(
+				app_dirs::AppDataType::UserData,
+				&app_dirs::AppInfo { name: version.executable_name, author: version.author },
+			).map_err(|e| format!("Cannot find the default base path: {}", e))?,
+		};
+		Ok(base_path.join("chains").join(spec.id()).join("keystore"))
+	}
+}
+
+/// The `generate-spec` command.
+#[derive(Debug, Clone, StructOpt)]
+pub struct GenerateSpecCmd {
+	/// The JSON description of the chain, see `res/staging.json` for an example.
+	#[structopt(parse(from_os_str))]
+	description: PathBuf,
+
+	/// Print the genesis storage instead of the genesis config.
+	#[structopt(long)]
+	raw: bool,
+}
+
+impl GenerateSpecCmd {
+	/// Print the chain spec, to be passed to `--chain` once saved.
+	fn run(self) -> error::Result<()> {
+		let json = std::fs::read(&self.description)
+			.map_err(|e| format!("Cannot read {}: {}", self.description.display(), e))?;
+		let spec = chain_spec::ChainDescription::from_json(&json)?.load()?;
+		println!("{}", spec.to_json(self.raw)?);
+		Ok(())
+	}
+}
+
+impl SignRequestCmd {
+	/// Build and sign the extrinsic like `Runtime::create_transaction` does, but immortal since
+	/// there is no chain state to pick a checkpoint block from.
+	fn run(self) -> error::Result<()> {
+		let pair = sr25519::Pair::from_string(&self.suri, self.password.as_deref())
+			.map_err(|e| format!("Invalid secret key URI: {:?}", e))?;
+		let genesis_hash = self.genesis_hash.trim_start_matches("0x").parse::<runtime::Hash>()
+			.map_err(|e| format!("Invalid genesis hash: {:?}", e))?;
+
+		let call = runtime::Call::Example(runtime::example::Call::request(self.value));
+		let extra = runtime::signed_extra(Era::Immortal, self.nonce, self.tip);
+		// Matches the `additional_signed` of every element of `SignedExtra`, which would need
+		// the chain state to compute.
+		let additional = (runtime::VERSION.spec_version, genesis_hash, genesis_hash, (), (), ());
+		let raw_payload = runtime::SignedPayload::from_raw(call, extra, additional);
+		let signature = raw_payload.using_encoded(|payload| pair.sign(payload));
+
+		let account = MultiSigner::from(pair.public()).into_account();
+		let (call, extra, _) = raw_payload.deconstruct();
+		let xt = runtime::UncheckedExtrinsic::new_signed(call, account.into(), signature.into(), extra);
+		println!("0x{}", HexDisplay::from(&xt.encode()));
+		Ok(())
+	}
+}
+
 /// Parse command line arguments into service configuration.
 pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()>
 where
@@ -22,8 +232,8 @@ where
 	E: IntoExit,
 {
 	type Config<T> = Configuration<(), T>;
-	match parse_and_prepare::<NoCustom, NoCustom, _>(&version, "substrate-node", args) {
-		ParseAndPrepare::Run(cmd) => cmd.run(load_spec, exit, |exit, _cli_args, _custom_args, config: Config<_>| {
+	match parse_and_prepare::<CustomSubcommands, NodeParams, _>(&version, "substrate-node", args) {
+		ParseAndPrepare::Run(cmd) => cmd.run(load_spec, exit, |exit, _cli_args, custom_args: NodeParams, config: Config<_>| {
 			info!("{}", version.name);
 			info!("  version {}", config.full_version());
 			info!("  by {}, 2019, 2020", version.author);
@@ -33,7 +243,7 @@ where
 			let runtime = Runtime::new().map_err(|e| format!("{:?}", e))?;
 			match config.roles {
 				ServiceRoles::LIGHT => run_until_exit(runtime, service::new_light(config)?, exit),
-				_ => run_until_exit(runtime, service::new_full(config)?, exit),
+				_ => run_until_exit(runtime, service::new_full(config, custom_args.suri()?, custom_args.instant_seal)?, exit),
 			}
 		}),
 		ParseAndPrepare::BuildSpec(cmd) => cmd.run::<NoCustom, _, _, _>(load_spec),
@@ -50,15 +260,21 @@ where
 		ParseAndPrepare::RevertChain(cmd) => {
 			cmd.run_with_builder(|config: Config<_>| Ok(new_full_start!(config).0), load_spec)
 		}
-		ParseAndPrepare::CustomCommand(_) => Ok(()),
+		ParseAndPrepare::CustomCommand(cmd) => match cmd {
+			CustomSubcommands::SignRequest(cmd) => cmd.run(),
+			CustomSubcommands::InsertOracleKey(cmd) => cmd.run(&version),
+			CustomSubcommands::GenerateSpec(cmd) => cmd.run(),
+		},
 	}?;
 
 	Ok(())
 }
 
+/// Load a preset by name, or else a JSON chain spec file by path.
 fn load_spec(id: &str) -> Result<Option<chain_spec::ChainSpec>, String> {
 	Ok(match chain_spec::Alternative::from(id) {
 		Some(spec) => Some(spec.load()?),
+		None if std::path::Path::new(id).is_file() => Some(chain_spec::ChainSpec::from_json_file(PathBuf::from(id))?),
 		None => None,
 	})
 }
@@ -7,6 +7,8 @@ mod chain_spec;
 #[macro_use]
 mod service;
 mod cli;
+#[cfg(test)]
+mod tests;
 
 pub use sc_cli::{error, IntoExit, VersionInfo};
 
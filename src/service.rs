@@ -1,4 +1,5 @@
 use grandpa::{self, FinalityProofProvider as GrandpaFinalityProofProvider};
+use parking_lot::Mutex;
 use runtime::{self, opaque::Block, GenesisConfig, RuntimeApi};
 use sc_basic_authority;
 use sc_client::LongestChain;
@@ -7,9 +8,13 @@ pub use sc_executor::NativeExecutor;
 use sc_network::construct_simple_protocol;
 use sc_service::{error::Error as ServiceError, AbstractService, Configuration, ServiceBuilder};
 use sp_consensus_aura::sr25519::AuthorityPair as AuraPair;
-use sp_inherents::InherentDataProviders;
+use sp_inherents::{InherentData, InherentDataProviders, InherentIdentifier, ProvideInherentData};
+use codec::Decode;
+use sp_consensus::SelectChain;
+use sp_core::storage::StorageKey;
+use sp_runtime::{app_crypto::AppKey, generic::BlockId, traits::Header as _, RuntimeString};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 // Our native executor instance.
 native_executor_instance!(
@@ -18,6 +23,9 @@ native_executor_instance!(
 	runtime::native_version,
 );
 
+/// The key type of the oracle authorities, as selected by the runtime.
+pub type OracleId = <runtime::Runtime as runtime::example::Trait>::AuthorityId;
+
 construct_simple_protocol! {
 	/// Demo protocol attachment for substrate.
 	pub struct NodeProtocol where Block = Block { }
@@ -76,9 +84,20 @@ macro_rules! new_full_start {
 }
 
 /// Builds a new service for a full client.
+///
+/// The oracle key of `oracle_key`, a secret key URI, is inserted into the keystore for good.
+/// With `instant_seal`, authorities seal a block as soon as a transaction enters the pool
+/// instead of running Aura and GRANDPA.
 pub fn new_full<C: Send + Default + 'static>(
 	config: Configuration<C, GenesisConfig>,
+	oracle_key: Option<String>,
+	instant_seal: bool,
 ) -> Result<impl AbstractService, ServiceError> {
+	// Instant seal disables GRANDPA, so a chain sealed this way never finalizes with the others.
+	if instant_seal && config.chain_spec.id() != "dev" {
+		return Err(format!("--instant-seal is only supported on the dev chain, not {:?}", config.chain_spec.id()).into());
+	}
+
 	let is_authority = config.roles.is_authority();
 	let force_authoring = config.force_authoring;
 	let name = config.name.clone();
@@ -103,7 +122,40 @@ pub fn new_full<C: Send + Default + 'static>(
 		})?
 		.build()?;
 
-	if participates_in_consensus {
+	if participates_in_consensus && instant_seal {
+		let proposer = sc_basic_authority::ProposerFactory {
+			client: service.client(),
+			transaction_pool: service.transaction_pool(),
+		};
+
+		let client = service.client();
+		let select_chain = service.select_chain().ok_or(ServiceError::SelectChainRequired)?;
+
+		// Without Aura, only the timestamp inherent is needed, but spaced by a slot at least,
+		// starting from the timestamp of the best block when the node restarts.
+		let inherent_data_providers = InherentDataProviders::new();
+		let slot_duration = sc_consensus_aura::SlotDuration::get_or_compute(&*client)?.get();
+		let best = select_chain.best_chain().map_err(|e| format!("{:?}", e))?;
+		let last = client
+			.storage(&BlockId::Hash(best.hash()), &StorageKey(runtime::timestamp_now_key()))?
+			.and_then(|now| u64::decode(&mut &now.0[..]).ok())
+			.unwrap_or(0);
+		inherent_data_providers
+			.register_provider(SlotTimestampProvider::new(slot_duration, last))
+			.map_err(|e| format!("{:?}", e))?;
+
+		let instant_seal = sc_consensus_manual_seal::run_instant_seal(
+			Box::new(block_import),
+			proposer,
+			client.backend().clone(),
+			service.transaction_pool().pool().pool().clone(),
+			select_chain,
+			inherent_data_providers,
+		);
+
+		// the sealing task is considered essential, like the AURA authoring task.
+		service.spawn_essential_task(instant_seal);
+	} else if participates_in_consensus {
 		let proposer = sc_basic_authority::ProposerFactory {
 			client: service.client(),
 			transaction_pool: service.transaction_pool(),
@@ -136,13 +188,21 @@ pub fn new_full<C: Send + Default + 'static>(
 		service
 			.keystore()
 			.write()
-			.insert_ephemeral_from_seed_by_type::<runtime::example::crypto::Pair>(
+			.insert_ephemeral_from_seed_by_type::<<OracleId as AppKey>::Pair>(
 				&seed,
 				runtime::example::crypto::KEY_TYPE,
 			)
 			.expect("Dev Seed always succeeds");
 	}
 
+	if let Some(suri) = oracle_key {
+		service
+			.keystore()
+			.write()
+			.insert_by_type::<<OracleId as AppKey>::Pair>(runtime::example::crypto::KEY_TYPE, &suri)
+			.map_err(|e| ServiceError::Other(format!("Cannot insert the oracle key: {}", e)))?;
+	}
+
 	// if the node isn't actively participating in consensus then it doesn't
 	// need a keystore, regardless of which protocol we use below.
 	let keystore = if participates_in_consensus {
@@ -161,7 +221,8 @@ pub fn new_full<C: Send + Default + 'static>(
 		is_authority,
 	};
 
-	match (is_authority, disable_grandpa) {
+	// instant seal blocks are final as soon as they are sealed.
+	match (is_authority, disable_grandpa || instant_seal) {
 		(false, false) => {
 			// start the lightweight GRANDPA observer
 			service.spawn_task(grandpa::run_grandpa_observer(
@@ -197,6 +258,42 @@ pub fn new_full<C: Send + Default + 'static>(
 	Ok(service)
 }
 
+/// Provides timestamps at least one Aura slot apart.
+///
+/// The Aura module accepts a single block per slot, while instant seal may author several
+/// blocks in a row, so the timestamps run ahead of the clock when blocks come faster than slots.
+struct SlotTimestampProvider {
+	slot_duration: u64,
+	last: Mutex<u64>,
+}
+
+impl SlotTimestampProvider {
+	/// Provide timestamps at least `slot_duration` after `last`, the timestamp of the best block.
+	fn new(slot_duration: u64, last: u64) -> Self {
+		SlotTimestampProvider { slot_duration, last: Mutex::new(last) }
+	}
+}
+
+impl ProvideInherentData for SlotTimestampProvider {
+	fn inherent_identifier(&self) -> &'static InherentIdentifier {
+		&sp_timestamp::INHERENT_IDENTIFIER
+	}
+
+	fn provide_inherent_data(&self, inherent_data: &mut InherentData) -> Result<(), RuntimeString> {
+		let now = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map_err(|_| "Current time is before the unix epoch")?
+			.as_millis() as u64;
+		let mut last = self.last.lock();
+		*last = now.max(*last + self.slot_duration);
+		inherent_data.put_data(sp_timestamp::INHERENT_IDENTIFIER, &*last)
+	}
+
+	fn error_to_string(&self, error: &[u8]) -> Option<String> {
+		sp_timestamp::InherentDataProvider.error_to_string(error)
+	}
+}
+
 /// Builds a new service for a light client.
 pub fn new_light<C: Send + Default + 'static>(
 	config: Configuration<C, GenesisConfig>,
@@ -0,0 +1,266 @@
+//! End-to-end tests of the oracle against the real `Runtime`, genesis and transaction format.
+
+use crate::chain_spec::{get_account_id_from_seed, Alternative, ChainDescription};
+use codec::Decode;
+use runtime::{
+	example, submitter::{SubmitAndSignTransaction, NONCE_EXPIRY}, timestamp_now_key, Call, Example, Executive,
+	GracePeriod, Header, Origin, Runtime, System, TimestampCall, TransactionMortality, UncheckedExtrinsic,
+	SLOT_DURATION,
+};
+use sp_core::{
+	offchain::{testing::{PoolState, TestOffchainExt, TestTransactionPoolExt}, OffchainExt, TransactionPoolExt},
+	sr25519, testing::KeyStore, traits::KeystoreExt,
+};
+use sp_runtime::{
+	traits::Header as _,
+	transaction_validity::{InvalidTransaction, TransactionValidityError},
+	BuildStorage,
+};
+use std::sync::Arc;
+
+/// Externalities with the development genesis and a `reqs` key for each of `seeds` in the
+/// keystore.
+fn new_test_ext(seeds: &[&str]) -> (sp_io::TestExternalities, Arc<parking_lot::RwLock<PoolState>>) {
+	let storage = Alternative::Development.load().unwrap().build_storage().unwrap();
+	let mut ext = sp_io::TestExternalities::new(storage);
+
+	let keystore = KeyStore::new();
+	for seed in seeds {
+		keystore.write().sr25519_generate_new(example::crypto::KEY_TYPE, Some(&format!("//{}", seed))).unwrap();
+	}
+	let (offchain, _) = TestOffchainExt::new();
+	let (pool, state) = TestTransactionPoolExt::new();
+	ext.register_extension(OffchainExt::new(offchain));
+	ext.register_extension(TransactionPoolExt::new(pool));
+	ext.register_extension(KeystoreExt(keystore));
+	(ext, state)
+}
+
+/// Initializes block 1 on top of genesis.
+fn initialize_block() {
+	let parent_hash = System::block_hash(0);
+	Executive::initialize_block(&Header::new(1, Default::default(), Default::default(), parent_hash, Default::default()));
+}
+
+/// Imports the block on top of `parent`, with its timestamp inherent followed by whatever
+/// `body` applies, and returns its header.
+fn import_block(parent: &Header, body: impl FnOnce()) -> Header {
+	let number = parent.number() + 1;
+	Executive::initialize_block(&Header::new(number, Default::default(), Default::default(), parent.hash(), Default::default()));
+	let timestamp = UncheckedExtrinsic::new_unsigned(Call::Timestamp(TimestampCall::set(number as u64 * SLOT_DURATION)));
+	assert_eq!(Executive::apply_extrinsic(timestamp), Ok(Ok(())));
+	body();
+	Executive::finalize_block()
+}
+
+/// Takes the extrinsics submitted to the pool so far.
+fn submitted(state: &Arc<parking_lot::RwLock<PoolState>>) -> Vec<UncheckedExtrinsic> {
+	state.write().transactions.drain(..).map(|tx| Decode::decode(&mut &*tx).unwrap()).collect()
+}
+
+#[test]
+fn offchain_worker_answers_through_the_runtime() {
+	let (mut ext, state) = new_test_ext(&["Alice", "Bob"]);
+	ext.execute_with(|| {
+		let alice = get_account_id_from_seed::<sr25519::Public>("Alice");
+		initialize_block();
+		assert!(Example::request(Origin::signed(alice), 100).is_ok());
+
+		Executive::offchain_worker(1);
+		let extrinsics = submitted(&state);
+		assert_eq!(extrinsics.len(), 1);
+		assert!(extrinsics[0].signature.is_some());
+
+		assert_eq!(Executive::apply_extrinsic(extrinsics[0].clone()), Ok(Ok(())));
+		assert_eq!(Example::results(), vec![(0, 100)]);
+		assert!(Example::data_request(0).is_none());
+	});
+}
+
+#[test]
+fn tampered_answers_are_rejected() {
+	let (mut ext, state) = new_test_ext(&["Alice", "Bob"]);
+	ext.execute_with(|| {
+		let alice = get_account_id_from_seed::<sr25519::Public>("Alice");
+		initialize_block();
+		assert!(Example::request(Origin::signed(alice), 100).is_ok());
+
+		Executive::offchain_worker(1);
+		let mut extrinsic = submitted(&state).remove(0);
+		extrinsic.function = runtime::Call::Example(example::Call::store_result(0, 999));
+
+		assert_eq!(
+			Executive::apply_extrinsic(extrinsic),
+			Err(TransactionValidityError::Invalid(InvalidTransaction::BadProof)),
+		);
+		assert!(Example::results().is_empty());
+	});
+}
+
+#[test]
+fn no_answer_without_an_oracle_key() {
+	let (mut ext, state) = new_test_ext(&["Charlie"]);
+	ext.execute_with(|| {
+		let alice = get_account_id_from_seed::<sr25519::Public>("Alice");
+		initialize_block();
+		assert!(Example::request(Origin::signed(alice), 100).is_ok());
+
+		Executive::offchain_worker(1);
+		assert!(submitted(&state).is_empty());
+	});
+}
+
+#[test]
+fn presets_separate_oracles_from_endowed_accounts() {
+	let account = |seed| get_account_id_from_seed::<sr25519::Public>(seed);
+	let cases = vec![
+		(Alternative::Development, vec![account("Alice")]),
+		(Alternative::LocalTestnet, vec![account("Alice"), account("Bob")]),
+		(Alternative::MultiNodeTestnet, vec![account("Alice"), account("Bob")]),
+	];
+	for (preset, oracles) in cases {
+		let storage = preset.load().unwrap().build_storage().unwrap();
+		sp_io::TestExternalities::new(storage).execute_with(|| {
+			assert_eq!(Example::authorities(), oracles);
+		});
+	}
+	sp_io::TestExternalities::new(Alternative::MultiNodeTestnet.load().unwrap().build_storage().unwrap())
+		.execute_with(|| {
+			// Eve is funded but not an oracle.
+			assert!(runtime::Balances::free_balance(account("Eve")) > 0);
+		});
+}
+
+#[test]
+fn staging_description_builds_a_spec() {
+	let storage = Alternative::Staging.load().unwrap().build_storage().unwrap();
+	sp_io::TestExternalities::new(storage).execute_with(|| {
+		let account = |seed| get_account_id_from_seed::<sr25519::Public>(seed);
+		assert_eq!(Example::authorities(), vec![account("Alice"), account("Bob")]);
+	});
+}
+
+#[test]
+fn descriptions_are_validated() {
+	let description = br#"{
+		"name": "Broken",
+		"id": "broken",
+		"authorities": ["//Alice"],
+		"oracles": ["not an account"],
+		"balances": [],
+		"sudo": "//Alice"
+	}"#;
+	assert!(ChainDescription::from_json(description).unwrap().load().is_err());
+	assert!(ChainDescription::from_json(br#"{ "name": "Incomplete" }"#).is_err());
+}
+
+#[test]
+fn descriptions_accept_balances_beyond_u64() {
+	let description = br#"{
+		"name": "Rich",
+		"id": "rich",
+		"authorities": ["//Alice"],
+		"oracles": [],
+		"balances": [["//Alice", 1000], ["//Bob", "1000000000000000000000000"]],
+		"sudo": "//Alice"
+	}"#;
+	let storage = ChainDescription::from_json(description).unwrap().load().unwrap().build_storage().unwrap();
+	sp_io::TestExternalities::new(storage).execute_with(|| {
+		let account = |seed| get_account_id_from_seed::<sr25519::Public>(seed);
+		assert_eq!(runtime::Balances::free_balance(account("Alice")), 1000);
+		assert_eq!(runtime::Balances::free_balance(account("Bob")), 1_000_000_000_000_000_000_000_000);
+	});
+
+	let invalid = br#"{
+		"name": "Broken",
+		"id": "broken",
+		"authorities": ["//Alice"],
+		"oracles": [],
+		"balances": [["//Alice", "a lot"]],
+		"sudo": "//Alice"
+	}"#;
+	assert!(ChainDescription::from_json(invalid).unwrap().load().is_err());
+}
+
+#[test]
+fn answers_requests_past_the_mortality_period() {
+	let (mut ext, state) = new_test_ext(&["Alice"]);
+	ext.execute_with(|| {
+		let alice = get_account_id_from_seed::<sr25519::Public>("Alice");
+		let last = TransactionMortality::get() as u32 + 2;
+		let mut head = Header::new(0, Default::default(), Default::default(), Default::default(), Default::default());
+		let mut pending = vec![];
+
+		// Every block answers the request of the previous one, with a transaction signed by
+		// the offchain worker after the previous block was finalized.
+		for number in 1..=last {
+			head = import_block(&head, || {
+				for extrinsic in pending.drain(..) {
+					assert_eq!(Executive::apply_extrinsic(extrinsic), Ok(Ok(())), "in block {}", number);
+				}
+				assert!(Example::request(Origin::signed(alice.clone()), number.into()).is_ok());
+			});
+			Executive::offchain_worker(number);
+			pending = submitted(&state);
+			assert_eq!(pending.len(), 1);
+		}
+
+		assert_eq!(Example::results().len(), last as usize - 1);
+	});
+}
+
+#[test]
+fn a_lost_answer_does_not_stall_the_oracle() {
+	let (mut ext, state) = new_test_ext(&["Alice"]);
+	ext.execute_with(|| {
+		let alice = get_account_id_from_seed::<sr25519::Public>("Alice");
+		let genesis = Header::new(0, Default::default(), Default::default(), Default::default(), Default::default());
+		let mut head = import_block(&genesis, || {
+			assert!(Example::request(Origin::signed(alice.clone()), 100).is_ok());
+		});
+
+		// Answer the request twice, with consecutive nonces.
+		Executive::offchain_worker(1);
+		let (_, key) = Example::authority_id(0, 1).unwrap();
+		let answer = example::Call::store_result(0, 100);
+		type Submitter = <Runtime as example::Trait>::SubmitTransaction;
+		assert_eq!(<Submitter as SubmitAndSignTransaction<Runtime, Call>>::sign_and_submit(answer, key), Ok(()));
+		let answers = submitted(&state);
+		assert_eq!(answers.len(), 2);
+
+		// The first answer is included; the second one can never be, its request being settled.
+		head = import_block(&head, || {
+			assert_eq!(Executive::apply_extrinsic(answers[0].clone()), Ok(Ok(())));
+			assert!(Example::data_request(0).is_none());
+			assert!(Example::request(Origin::signed(alice.clone()), 200).is_ok());
+		});
+
+		// The worker gives up on the lost nonce and answers the next request.
+		let last = *head.number() + NONCE_EXPIRY + GracePeriod::get() + 1;
+		for number in *head.number()..last {
+			Executive::offchain_worker(number);
+			let pending = submitted(&state);
+			head = import_block(&head, || {
+				// Answers with a nonce after the lost one fail, like the pool would drop them.
+				for extrinsic in pending {
+					let _ = Executive::apply_extrinsic(extrinsic);
+				}
+			});
+			if Example::data_request(1).is_none() {
+				break;
+			}
+		}
+		assert_eq!(Example::results(), vec![(0, 100), (1, 200)]);
+	});
+}
+
+#[test]
+fn block_timestamps_are_read_back_from_the_state() {
+	let (mut ext, _) = new_test_ext(&[]);
+	ext.execute_with(|| {
+		let genesis = Header::new(0, Default::default(), Default::default(), Default::default(), Default::default());
+		import_block(&genesis, || ());
+		let now = sp_io::storage::get(&timestamp_now_key()).and_then(|now| u64::decode(&mut &now[..]).ok());
+		assert_eq!(now, Some(SLOT_DURATION));
+	});
+}